@@ -0,0 +1,105 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use mongodb::{bson::doc, Database};
+use poise::serenity_prelude::UserId;
+use serde::{Deserialize, Serialize};
+use sha256::digest;
+
+use crate::{
+    models::{DBCollective, DBMate},
+    utils::{front::switched_in_since, mates::get_switched_in_mate},
+};
+
+pub fn router(database: Database) -> Router {
+    Router::new()
+        .route("/fronters/:user_id", get(fronters))
+        .with_state(database)
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct FrontersResponse {
+    user_id: String,
+    fronters: Vec<Fronter>,
+    switched_in_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct Fronter {
+    name: String,
+    display_name: Option<String>,
+    pronouns: Option<String>,
+    avatar_url: String,
+}
+
+/// Read-only view of who's switched in, for collectives that have opted in with `/front api`.
+/// The token can be passed as `?token=` or as an `Authorization: Bearer` header
+async fn fronters(
+    State(database): State<Database>,
+    Path(user_id): Path<u64>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+) -> Result<Json<FrontersResponse>, StatusCode> {
+    if user_id == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .map(|header| header.trim_start_matches("Bearer ").to_string())
+        .or(query.token)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let collective = collectives_collection
+        .find_one(
+            doc! { "$or": [
+                { "user_id": user_id as i64 },
+                { "linked_accounts": user_id as i64 }
+            ] },
+            None,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // collectives that haven't opted in look the same as ones that don't exist
+    let token_digest = collective
+        .front_api_token
+        .clone()
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if digest(token) != token_digest {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let mate = get_switched_in_mate(&mates_collection, UserId::new(collective.user_id as u64))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(FrontersResponse {
+        user_id: user_id.to_string(),
+        fronters: mate
+            .into_iter()
+            .map(|mate| Fronter {
+                name: mate.name,
+                display_name: mate.display_name,
+                pronouns: mate.pronouns,
+                avatar_url: mate.avatar,
+            })
+            .collect(),
+        switched_in_since: switched_in_since(&collective),
+    }))
+}
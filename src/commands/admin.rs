@@ -10,7 +10,7 @@ use crate::{
 
 // if required_permissions ever doesn't work or breaks we are FUCKED (capital-er f)
 
-#[poise::command(slash_command, subcommands("proxy_logs", "allowlist"))]
+#[poise::command(slash_command, subcommands("proxy_logs", "allowlist", "birthdays"))]
 pub async fn admin(_ctx: CommandContext<'_>) -> Result<()> {
     unreachable!()
 }
@@ -95,3 +95,47 @@ pub async fn proxy_logs(
 
     Ok(())
 }
+
+/// Announce the birthdays of everyone who opts in (with `/settings birthdays`) in a channel
+#[poise::command(slash_command, ephemeral, required_permissions = "MANAGE_GUILD")]
+pub async fn birthdays(
+    ctx: CommandContext<'_>,
+    #[description = "the channel to announce birthdays in (resets to none, if unspecified)"]
+    channel: Option<serenity::Channel>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+
+    let guilds_collection = database.collection::<DBGuild>("guilds");
+
+    let mut guild = get_or_create_dbguild(
+        &guilds_collection,
+        ctx.guild_id()
+            .expect("Couldn't get the guild id! Are you running this command in DMs?")
+            .get() as i64,
+    )
+    .await?;
+
+    guild.birthday_channel_id = channel.as_ref().map(|channel| channel.id().get() as i64);
+
+    guilds_collection
+        .update_one(
+            doc! {
+                "id": guild.id
+            },
+            doc! { "$set": bson::to_bson(&guild).unwrap() },
+            None,
+        )
+        .await?;
+
+    if let Some(channel) = channel {
+        ctx.say(format!(
+            "Set birthday announcement channel to <#{}>",
+            channel.id()
+        ))
+        .await?;
+    } else {
+        ctx.say("Disabled birthday announcements!").await?;
+    }
+
+    Ok(())
+}
@@ -1,31 +1,103 @@
+use chrono_tz::TZ_VARIANTS;
 use mongodb::bson::doc;
 use strsim::normalized_damerau_levenshtein;
 
 use super::CommandContext;
-use crate::models::DBMate;
+use crate::{
+    models::{DBCollective, DBGroup, DBMate, DBTrashedMate},
+    utils::collectives::get_owner_id,
+};
 
 pub async fn mate(ctx: CommandContext<'_>, current_arg: &str) -> Vec<String> {
     let database = &ctx.data().database;
     let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id)
+        .await
+        .unwrap_or(ctx.author().id);
 
     let mut mates: Vec<String> = mates_collection
-        .distinct(
-            "name",
-            doc! { "user_id": ctx.author().id.get() as i64 },
-            None,
-        )
+        .distinct("name", doc! { "user_id": user_id.get() as i64 }, None)
         .await
         .expect("Failed to get all mates!")
         .iter()
         .map(|bson| bson.as_str().unwrap().to_string())
         .collect();
 
-    mates.sort_by(|a, b| {
-        normalized_damerau_levenshtein(b, current_arg)
-            .partial_cmp(&normalized_damerau_levenshtein(a, current_arg))
-            .unwrap()
-    });
-    mates.shrink_to(25);
+    sort_by_similarity(&mut mates, current_arg);
 
     mates
 }
+
+pub async fn trashed_mate(ctx: CommandContext<'_>, current_arg: &str) -> Vec<String> {
+    let database = &ctx.data().database;
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id)
+        .await
+        .unwrap_or(ctx.author().id);
+
+    let mut mates: Vec<String> = trash_collection
+        .distinct("name", doc! { "user_id": user_id.get() as i64 }, None)
+        .await
+        .expect("Failed to get deleted mates!")
+        .iter()
+        .map(|bson| bson.as_str().unwrap().to_string())
+        .collect();
+
+    sort_by_similarity(&mut mates, current_arg);
+
+    mates
+}
+
+pub async fn group(ctx: CommandContext<'_>, current_arg: &str) -> Vec<String> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id)
+        .await
+        .unwrap_or(ctx.author().id);
+
+    let mut groups: Vec<String> = groups_collection
+        .distinct("name", doc! { "user_id": user_id.get() as i64 }, None)
+        .await
+        .expect("Failed to get all groups!")
+        .iter()
+        .map(|bson| bson.as_str().unwrap().to_string())
+        .collect();
+
+    sort_by_similarity(&mut groups, current_arg);
+
+    groups
+}
+
+pub async fn timezone(_ctx: CommandContext<'_>, current_arg: &str) -> Vec<String> {
+    let current_arg = current_arg.to_lowercase();
+
+    let mut timezones = TZ_VARIANTS
+        .iter()
+        .map(|timezone| timezone.name().to_string())
+        .filter(|timezone| timezone.to_lowercase().contains(&current_arg))
+        .collect::<Vec<_>>();
+
+    sort_by_similarity(&mut timezones, &current_arg);
+
+    timezones
+}
+
+/// How closely a name matches what's been typed so far, from 0 to 1
+pub fn similarity(name: &str, current_arg: &str) -> f64 {
+    normalized_damerau_levenshtein(name, current_arg)
+}
+
+fn sort_by_similarity(names: &mut Vec<String>, current_arg: &str) {
+    names.sort_by(|a, b| {
+        similarity(b, current_arg)
+            .partial_cmp(&similarity(a, current_arg))
+            .unwrap()
+    });
+    names.shrink_to(25);
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+use super::CommandContext;
+use crate::{
+    models::{DBBackup, DBCollective},
+    utils::{
+        backups::{get_backups, max_backups, restore_backup},
+        collectives::get_owner_id,
+    },
+};
+
+#[poise::command(slash_command, subcommands("list", "restore"))]
+pub async fn backup(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// List the backups made before your collective was reset or overwritten
+#[poise::command(slash_command, ephemeral)]
+pub async fn list(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let backups_collection = database.collection::<DBBackup>("backups");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let backups = get_backups(&backups_collection, user_id).await?;
+
+    if backups.is_empty() {
+        ctx.say(
+            "You don't have any backups yet! One is made automatically whenever your collective \
+             is about to be reset or overwritten by an import.",
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let lines = backups
+        .iter()
+        .enumerate()
+        .map(|(i, backup)| {
+            format!(
+                "**{}.** <t:{}:f> ({}, {} mates)",
+                i + 1,
+                backup.created_at.timestamp(),
+                backup.reason,
+                backup
+                    .collections
+                    .get_array("mates")
+                    .map(|mates| mates.len())
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let embed = CreateEmbed::new()
+        .title("Backups")
+        .description(lines.join("\n"))
+        .footer(CreateEmbedFooter::new(format!(
+            "The last {} backups are kept; bring one back with /backup restore",
+            max_backups()
+        )));
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Put your collective back how it was in a backup (what's there now is backed up first)
+#[poise::command(slash_command, ephemeral)]
+pub async fn restore(
+    ctx: CommandContext<'_>,
+    #[description = "which backup to restore, as numbered in /backup list"]
+    #[min = 1]
+    number: usize,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let backups_collection = database.collection::<DBBackup>("backups");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let backup = get_backups(&backups_collection, user_id)
+        .await?
+        .into_iter()
+        .nth(number - 1)
+        .context("There's no backup with that number; check `/backup list`!")?;
+
+    restore_backup(database, &backup).await?;
+
+    ctx.say(format!(
+        "Restored the backup from <t:{}:f>! If that wasn't the one you wanted, your collective \
+         was backed up first, so it's now number 1 in `/backup list`.",
+        backup.created_at.timestamp()
+    ))
+    .await?;
+
+    Ok(())
+}
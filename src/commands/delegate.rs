@@ -0,0 +1,189 @@
+use anyhow::{bail, Result};
+use mongodb::bson::{self, doc};
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed, UserId},
+    CreateReply,
+};
+
+use super::CommandContext;
+use crate::{
+    models::{DBCollective, DBMate, Delegate},
+    utils::{
+        collectives::get_or_create_collective,
+        mates::{get_all_mates, get_mate},
+    },
+};
+
+#[poise::command(slash_command, subcommands("set", "remove", "list"))]
+pub async fn delegate(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Let someone else manage your messages or proxy as some of your mates (ie a GM)
+#[poise::command(slash_command, ephemeral)]
+pub async fn set(
+    ctx: CommandContext<'_>,
+    #[description = "the person to make a delegate (replaces their permissions if they already \
+                     are)"]
+    user: serenity::User,
+    #[description = "whether they can edit your proxied messages"] edit_messages: Option<bool>,
+    #[description = "whether they can delete your proxied messages"] delete_messages: Option<bool>,
+    #[description = "the mates they can proxy as, separated by commas (ie `Alex, Sam`)"]
+    mates: Option<String>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if user.bot {
+        bail!("You can't make a bot a delegate!");
+    }
+
+    if collective.accounts().contains(&(user.id.get() as i64)) {
+        bail!("That account is already part of your collective!");
+    }
+
+    let mut proxy_mate_ids = vec![];
+
+    for name in mates
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    {
+        let Some(mate) = get_mate(
+            &mates_collection,
+            UserId::new(collective.user_id as u64),
+            name.clone(),
+        )
+        .await
+        else {
+            bail!("Failed to find mate `{}`; do they actually exist?", name);
+        };
+
+        // impossible for the id to not exist
+        proxy_mate_ids.push(mate.id.unwrap());
+    }
+
+    let new_delegate = Delegate {
+        user_id: user.id.get() as i64,
+        edit_messages: edit_messages.unwrap_or(false),
+        delete_messages: delete_messages.unwrap_or(false),
+        proxy_mate_ids,
+    };
+
+    if !new_delegate.edit_messages
+        && !new_delegate.delete_messages
+        && new_delegate.proxy_mate_ids.is_empty()
+    {
+        bail!("A delegate needs at least one permission! Use `/delegate remove` to remove one.");
+    }
+
+    let mut delegates = collective.delegates.unwrap_or_default();
+    delegates.retain(|delegate| delegate.user_id != new_delegate.user_id);
+    delegates.push(new_delegate);
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$set": { "delegates": bson::to_bson(&delegates).unwrap() } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!("Made <@{}> a delegate!", user.id)).await?;
+
+    Ok(())
+}
+
+/// Stop someone from managing your messages or proxying as your mates
+#[poise::command(slash_command, ephemeral)]
+pub async fn remove(
+    ctx: CommandContext<'_>,
+    #[description = "the delegate to remove"] user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if !collective
+        .delegates
+        .unwrap_or_default()
+        .iter()
+        .any(|delegate| delegate.user_id == user.id.get() as i64)
+    {
+        bail!("That person isn't one of your delegates!");
+    }
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$pull": { "delegates": { "user_id": user.id.get() as i64 } } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!("<@{}> is no longer a delegate!", user.id))
+        .await?;
+
+    Ok(())
+}
+
+/// List everyone who can act on behalf of your collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn list(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let delegates = collective.delegates.unwrap_or_default();
+    let mates = get_all_mates(&mates_collection, UserId::new(collective.user_id as u64)).await?;
+
+    if delegates.is_empty() {
+        ctx.say("You don't have any delegates!").await?;
+
+        return Ok(());
+    }
+
+    let lines = delegates
+        .iter()
+        .map(|delegate| {
+            let mut permissions = vec![];
+
+            if delegate.edit_messages {
+                permissions.push("edit messages".to_string());
+            }
+            if delegate.delete_messages {
+                permissions.push("delete messages".to_string());
+            }
+            // deleted mates are left out, but come back if the mate's restored
+            let proxy_mates = mates
+                .iter()
+                .filter(|mate| {
+                    mate.id
+                        .is_some_and(|id| delegate.proxy_mate_ids.contains(&id))
+                })
+                .map(|mate| mate.name.clone())
+                .collect::<Vec<_>>();
+
+            if !proxy_mates.is_empty() {
+                permissions.push(format!("proxy as {}", proxy_mates.join(", ")));
+            }
+
+            format!("<@{}>: {}", delegate.user_id, permissions.join("; "))
+        })
+        .collect::<Vec<_>>();
+
+    let embed = CreateEmbed::new()
+        .title("Delegates")
+        .description(lines.join("\n"));
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
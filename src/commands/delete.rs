@@ -4,21 +4,27 @@ use poise::serenity_prelude::MessageId;
 
 use super::{autocomplete::mate as mate_autocomplete, CommandContext};
 use crate::{
-    models::{DBChannel, DBMate, DBMessage},
+    models::{
+        DBChannel, DBCollective, DBMate, DBMateTombstone, DBMessage, DBTrashedMate,
+        DelegatePermission,
+    },
     utils::{
         channels::get_webhook_or_create,
-        mates::{delete_mate, get_mate},
+        collectives::{get_or_create_collective, get_owner_id},
+        front::handle_front_change,
+        mates::get_switched_in_mate,
         messages::{delete_dbmessage, get_message, get_most_recent_message},
         misc::message_link_to_id,
+        trash::{get_trashed_mates, restore_mate, retention_days, trash_mate},
     },
 };
 
-#[poise::command(slash_command, subcommands("mate", "message"))]
+#[poise::command(slash_command, subcommands("mate", "message", "undo"))]
 pub async fn delete(_ctx: CommandContext<'_>) -> Result<()> {
     unreachable!()
 }
 
-/// Delete a mate
+/// Delete a mate (they can be brought back for a while with `/delete undo` or `/trash restore`)
 #[poise::command(slash_command, ephemeral)]
 pub async fn mate(
     ctx: CommandContext<'_>,
@@ -29,14 +35,67 @@ pub async fn mate(
     let database = &ctx.data().database;
 
     let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let previous_mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    trash_mate(
+        &mates_collection,
+        &tombstones_collection,
+        &trash_collection,
+        user_id,
+        name.clone(),
+    )
+    .await?;
 
-    get_mate(&mates_collection, ctx.author().id, name.clone())
-        .await
-        .context("Failed to find mate; do they actually exist?")?;
+    // deleting whoever's switched in switches them out
+    let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
 
-    delete_mate(&mates_collection, ctx.author().id, name.clone()).await?;
+    handle_front_change(ctx.http(), database, &collective, previous_mate, mate).await?;
 
-    ctx.say("Successfully deleted mate! o7 :headstone:").await?;
+    ctx.say(format!(
+        "Successfully deleted mate! o7 :headstone:\nChanged your mind? `/delete undo` brings them \
+         back, for the next {} days.",
+        retention_days()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Bring back the most recently deleted mate
+#[poise::command(slash_command, ephemeral)]
+pub async fn undo(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+
+    let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let trashed = get_trashed_mates(&trash_collection, user_id)
+        .await?
+        .into_iter()
+        .next()
+        .context("There's nothing to undo; the trash is empty!")?;
+
+    let name = trashed.name.clone();
+
+    restore_mate(
+        &mates_collection,
+        &tombstones_collection,
+        &trash_collection,
+        trashed,
+    )
+    .await?;
+
+    ctx.say(format!("Brought {} back!", name)).await?;
     Ok(())
 }
 
@@ -49,6 +108,9 @@ pub async fn message(
     let database = &ctx.data().database;
     let channels_collection = database.collection::<DBChannel>("channels");
     let messages_collection = database.collection::<DBMessage>("messages");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
 
     let message_to_delete_id;
     if let Some(message_id) = message_id {
@@ -56,7 +118,7 @@ pub async fn message(
     } else if let Some(message_link) = message_link {
         message_to_delete_id = message_link_to_id(message_link)?
     } else {
-        let message = get_most_recent_message(&messages_collection, ctx.author().id).await?;
+        let message = get_most_recent_message(&messages_collection, user_id).await?;
         message_to_delete_id = MessageId::new(message.message_id)
     }
 
@@ -65,7 +127,9 @@ pub async fn message(
 
     let dbmessage = get_message(
         &messages_collection,
+        &collectives_collection,
         Some(ctx.author().id),
+        DelegatePermission::DeleteMessages,
         message_to_delete_id,
     )
     .await;
@@ -2,15 +2,20 @@ use anyhow::{Context, Result};
 use mongodb::bson::doc;
 use poise::serenity_prelude::{self as serenity, EditWebhookMessage, MessageId};
 
-use super::{autocomplete::mate as mate_autocomplete, CommandContext};
+use super::{
+    autocomplete::{mate as mate_autocomplete, timezone as timezone_autocomplete},
+    CommandContext,
+};
 use crate::{
-    models::{DBChannel, DBCollective, DBMate, DBMessage},
+    models::{DBChannel, DBCollective, DBMate, DBMessage, DelegatePermission},
     utils::{
+        avatars::{rehost_image, upload_avatar, upload_banner, ImageKind},
         channels::get_webhook_or_create,
-        collectives::get_or_create_collective,
+        collectives::{get_or_create_collective, get_owner_id},
         mates::get_mate,
         messages::{get_message, get_most_recent_message},
-        misc::{message_link_to_id, upload_avatar},
+        misc::{message_link_to_id, parse_color},
+        time::{parse_birthday, parse_timezone},
     },
 };
 
@@ -33,31 +38,46 @@ pub async fn mate(
     >,
     #[description = "the new avatar to use when proxying"] avatar: Option<serenity::Attachment>,
     #[description = "a link to an avatar to use when proxying"] avatar_url: Option<String>,
+    #[description = "whether to copy the linked avatar over to Multiplex so it can't expire \
+                     (defaults to true)"]
+    rehost_avatar: Option<bool>,
     #[description = "the mate's bio"] bio: Option<String>,
     #[description = "the mate's pronouns"] pronouns: Option<String>,
     #[description = "a signature to add to any proxied messages (ie `💙- text`)"] signature: Option<
         String,
     >,
+    #[description = "the mate's colour, as a hex code (ie `#ff8800`)"] color: Option<String>,
+    #[description = "the mate's birthday (ie `2001-04-08`, or `04-08` without a year)"]
+    birthday: Option<String>,
+    #[description = "the new banner to show on /info"] banner: Option<serenity::Attachment>,
 ) -> Result<()> {
     let database = &ctx.data().database;
 
     let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
 
-    let mut avatar_url = avatar_url;
+    let mut avatar_url = match avatar_url {
+        Some(url) if rehost_avatar.unwrap_or(true) => {
+            Some(rehost_image(ctx.data().avatar_storage.as_ref(), &url, ImageKind::Avatar).await?)
+        }
+        avatar_url => avatar_url,
+    };
 
     if let Some(avatar) = avatar {
-        avatar_url = Some(
-            upload_avatar(
-                &ctx.data().avatar_bucket,
-                ctx.author().id,
-                name.clone(),
-                avatar,
-            )
-            .await?,
-        )
+        avatar_url = Some(upload_avatar(ctx.data().avatar_storage.as_ref(), avatar).await?)
     }
 
-    get_mate(&mates_collection, ctx.author().id, name.clone())
+    let color = color.as_deref().map(parse_color).transpose()?;
+    let birthday = birthday.as_deref().map(parse_birthday).transpose()?;
+    let banner = if let Some(banner) = banner {
+        Some(upload_banner(ctx.data().avatar_storage.as_ref(), banner).await?)
+    } else {
+        None
+    };
+
+    get_mate(&mates_collection, user_id, name.clone())
         .await
         .context("Failed to find mate to edit; does it exist?")?
         .edit(
@@ -70,6 +90,9 @@ pub async fn mate(
             publicity,
             avatar_url,
             signature,
+            color,
+            birthday,
+            banner,
         )
         .await?;
 
@@ -90,13 +113,33 @@ pub async fn collective(
     #[description = "A tag to append to all proxied mates, to identify your collective in chat"]
     collective_tag: Option<String>,
     #[description = "If true, remove your collective tag"] remove_collective_tag: Option<bool>,
+    #[description = "your collective's colour, as a hex code (ie `#ff8800`)"] color: Option<String>,
+    #[description = "your collective's birthday (ie `2001-04-08`, or `04-08` without a year)"]
+    birthday: Option<String>,
+    #[description = "a banner to show on /info"] banner: Option<serenity::Attachment>,
+    #[description = "your timezone, used when showing and entering times (ie `Europe/London`)"]
+    #[autocomplete = "timezone_autocomplete"]
+    timezone: Option<String>,
 ) -> Result<()> {
     let database = &ctx.data().database;
 
     let collectives_collection = database.collection::<DBCollective>("collectives");
 
-    get_or_create_collective(&collectives_collection, ctx.author().id)
-        .await?
+    let mut collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let color = color.as_deref().map(parse_color).transpose()?;
+    let birthday = birthday.as_deref().map(parse_birthday).transpose()?;
+    let timezone = timezone
+        .as_deref()
+        .map(|timezone| parse_timezone(timezone).map(|timezone| timezone.name().to_string()))
+        .transpose()?;
+    let banner = if let Some(banner) = banner {
+        Some(upload_banner(ctx.data().avatar_storage.as_ref(), banner).await?)
+    } else {
+        None
+    };
+
+    collective
         .edit(
             collectives_collection,
             name,
@@ -108,6 +151,10 @@ pub async fn collective(
             } else {
                 collective_tag
             },
+            color,
+            birthday,
+            banner,
+            timezone,
         )
         .await?;
 
@@ -127,6 +174,9 @@ pub async fn message(
     let database = &ctx.data().database;
     let channels_collection = database.collection::<DBChannel>("channels");
     let messages_collection = database.collection::<DBMessage>("messages");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
 
     let message_to_edit_id;
     if let Some(message_id) = message_id {
@@ -134,7 +184,7 @@ pub async fn message(
     } else if let Some(message_link) = message_link {
         message_to_edit_id = message_link_to_id(message_link)?
     } else {
-        let message = get_most_recent_message(&messages_collection, ctx.author().id).await?;
+        let message = get_most_recent_message(&messages_collection, user_id).await?;
         message_to_edit_id = MessageId::new(message.message_id)
     }
 
@@ -143,7 +193,9 @@ pub async fn message(
 
     _ = get_message(
         &messages_collection,
+        &collectives_collection,
         Some(ctx.author().id),
+        DelegatePermission::EditMessages,
         message_to_edit_id,
     )
     .await?;
@@ -1,45 +1,200 @@
-use anyhow::Result;
+use std::collections::HashSet;
+
+use anyhow::{bail, Context, Result};
 use mongodb::bson::doc;
-use poise::{serenity_prelude::CreateAttachment, CreateReply};
+use poise::{
+    futures_util::TryStreamExt,
+    serenity_prelude::{CreateAttachment, UserId},
+    ChoiceParameter, CreateReply,
+};
 
 use super::CommandContext;
 use crate::{
-    models::{DBCollective, DBMate},
-    pluralkit::{Member, MemberPrivacy, PluralkitExport, ProxyTag, SystemPrivacy},
-    utils::{collectives::get_or_create_collective, mates::get_all_mates},
+    models::{
+        DBCollective, DBGroup, DBMate, DBMateTombstone, DBMessage, DBTrashedMate, DBUserSettings,
+    },
+    multiplex::MultiplexExport,
+    pluralkit::{
+        Config, Group, GroupPrivacy, Member, MemberPrivacy, PluralkitExport, ProxyTag, Switch,
+        SystemPrivacy,
+    },
+    utils::{collectives::get_or_create_collective, groups::get_all_groups, mates::get_all_mates},
 };
 
-/// Export your collective to a format (theoretically) compatible with both Tupperbox and Pluralkit
+/// The most Discord lets a bot upload to a server without boosts
+const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, ChoiceParameter, PartialEq)]
+pub enum ExportFormat {
+    #[name = "Multiplex (everything, for importing back into Multiplex)"]
+    Multiplex,
+    #[name = "PluralKit (compatible with PluralKit and Tupperbox, but loses some data)"]
+    PluralKit,
+}
+
+/// Export your collective, to back it up or move it to another bot
 #[poise::command(slash_command, ephemeral)]
-pub async fn export(ctx: CommandContext<'_>) -> Result<()> {
+pub async fn export(
+    ctx: CommandContext<'_>,
+    #[description = "what format to export in (defaults to Multiplex)"] format: Option<
+        ExportFormat,
+    >,
+    #[description = "whether to include every proxied message (Multiplex format only)"]
+    include_messages: Option<bool>,
+) -> Result<()> {
     let database = &ctx.data().database;
 
     let collectives_collection = database.collection::<DBCollective>("collectives");
     let mates_collection = database.collection::<DBMate>("mates");
+    let groups_collection = database.collection::<DBGroup>("groups");
 
     let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+    let user_id = UserId::new(collective.user_id as u64);
+
+    let mates = get_all_mates(&mates_collection, user_id).await?;
+    let groups = get_all_groups(&groups_collection, user_id).await?;
 
-    let mates = get_all_mates(&mates_collection, ctx.author().id).await?;
+    let export = match format.unwrap_or(ExportFormat::Multiplex) {
+        ExportFormat::Multiplex => {
+            let filter = doc! { "user_id": collective.user_id };
 
-    let collective_privacy_str = if collective.is_public {
-        "public"
-    } else {
-        "private"
+            let tombstones = database
+                .collection::<DBMateTombstone>("tombstones")
+                .find(filter.clone(), None)
+                .await?
+                .try_collect::<Vec<_>>()
+                .await
+                .context("Failed to export deleted mates!")?;
+            let trash = database
+                .collection::<DBTrashedMate>("trash")
+                .find(filter.clone(), None)
+                .await?
+                .try_collect::<Vec<_>>()
+                .await
+                .context("Failed to export deleted mates!")?;
+            // there can be a lot of these, so they're left out unless they're asked for
+            let messages = if include_messages.unwrap_or(false) {
+                database
+                    .collection::<DBMessage>("messages")
+                    .find(filter, None)
+                    .await?
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .context("Failed to export proxied messages!")?
+            } else {
+                vec![]
+            };
+            // settings belong to each account rather than the collective
+            let settings = database
+                .collection::<DBUserSettings>("settings")
+                .find(doc! { "user_id": ctx.author().id.get() as i64 }, None)
+                .await?
+                .try_collect::<Vec<_>>()
+                .await
+                .context("Failed to export settings!")?;
+
+            serde_json::to_vec(&MultiplexExport::new(
+                collective, mates, groups, tombstones, trash, settings, messages,
+            ))?
+        }
+        ExportFormat::PluralKit => {
+            serde_json::to_vec(&to_pluralkit_export(collective, mates, groups))?
+        }
     };
 
-    let export = PluralkitExport {
+    if export.len() > MAX_ATTACHMENT_SIZE {
+        if include_messages == Some(true) {
+            bail!("Your export is too big for Discord to send! Try again without the messages.");
+        }
+
+        bail!("Your export is too big for Discord to send!");
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(
+                "Exported data! (Warning: This download may not work properly on mobile devices, \
+                 because Discord doesn't know how to program.)",
+            )
+            .attachment(CreateAttachment::bytes(
+                export,
+                "multiplex-export.json".to_string(),
+            )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// A format (theoretically) compatible with both Tupperbox and Pluralkit
+fn to_pluralkit_export(
+    collective: DBCollective,
+    mates: Vec<DBMate>,
+    groups: Vec<DBGroup>,
+) -> PluralkitExport<'static> {
+    let exported_mate_ids = mates
+        .iter()
+        .filter_map(|mate| mate.id)
+        .collect::<HashSet<_>>();
+
+    let switches = collective
+        .switch_logs
+        .iter()
+        .flatten()
+        .filter_map(|log| {
+            let members = match log.mate_id.filter(|_| !log.unswitch) {
+                // switches to mates that aren't exported would otherwise look like switch-outs
+                Some(mate_id) if !exported_mate_ids.contains(&mate_id) => return None,
+                Some(mate_id) => vec![mate_id.to_hex()],
+                None => vec![],
+            };
+
+            Some(Switch {
+                timestamp: log.date,
+                members,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let group_privacy = collective.privacy_settings().group_list.as_str();
+
+    let groups = groups
+        .into_iter()
+        .map(|group| Group {
+            // impossible for the id to not exist
+            id: group.id.unwrap().to_hex(),
+            uuid: Default::default(),
+            name: group.name,
+            display_name: None,
+            description: group.description,
+            icon: group.icon,
+            banner: None,
+            color: None,
+            privacy: GroupPrivacy::create_from_single(group_privacy),
+            members: group
+                .mate_ids
+                .iter()
+                .filter(|mate_id| exported_mate_ids.contains(mate_id))
+                .map(|mate_id| mate_id.to_hex())
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let collective_privacy = collective.privacy_settings();
+    let accounts = collective.accounts();
+    let timezone = collective.timezone();
+
+    PluralkitExport {
         version: 2,
         name: collective.name,
         description: collective.bio,
         // impossible for the id to not exist
         created: collective.id.unwrap().timestamp().to_chrono(),
         pronouns: collective.pronouns,
-        privacy: SystemPrivacy::create_from_single(collective_privacy_str),
+        privacy: SystemPrivacy::from_collective_privacy(&collective_privacy),
         members: mates
             .iter()
             .map(|mate| {
-                let privacy_str = if mate.is_public { "public" } else { "private" };
-
                 Member {
                     name: mate.name.clone(),
                     display_name: mate.display_name.clone(),
@@ -52,47 +207,36 @@ pub async fn export(ctx: CommandContext<'_>) -> Result<()> {
                         prefix: mate.prefix.clone(),
                         suffix: mate.postfix.clone(),
                     }],
-                    privacy: MemberPrivacy::create_from_single(privacy_str),
+                    privacy: MemberPrivacy::from_mate(mate),
+                    color: mate.color.clone(),
+                    birthday: mate.birthday.map(|birthday| birthday.to_string()),
+                    banner: mate.banner.clone(),
                     // useless pluralkit garbage
                     autoproxy_enabled: true,
                     keep_proxy: false,
-                    banner: None,
-                    birthday: None,
-                    color: None,
                     webhook_avatar_url: None,
                     message_count: 0,
                     last_message_timestamp: None,
-                    id: Default::default(),
+                    // referenced by the exported switches
+                    id: mate.id.unwrap().to_hex(),
                     uuid: Default::default(),
                 }
             })
             .collect::<Vec<Member>>(),
         tag: collective.collective_tag,
+        color: collective.color,
+        banner: collective.banner,
         // useless pluralkit garbage: part 2
         avatar_url: None,
         id: Default::default(),
         uuid: Default::default(),
-        banner: None,
-        color: None,
         webhook_url: None,
-        config: Default::default(),
-        accounts: vec![],
-        groups: vec![],
-        switches: vec![],
-    };
-
-    ctx.send(
-        CreateReply::default()
-            .content(
-                "Exported data! (Warning: This download may not work properly on mobile devices, \
-                 because Discord doesn't know how to program.)",
-            )
-            .attachment(CreateAttachment::bytes(
-                serde_json::to_vec(&export).unwrap(),
-                "multiplex-export.json".to_string(),
-            )),
-    )
-    .await?;
-
-    Ok(())
+        config: Config {
+            timezone: timezone.name().to_string(),
+            ..Default::default()
+        },
+        accounts,
+        groups,
+        switches,
+    }
 }
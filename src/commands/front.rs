@@ -0,0 +1,268 @@
+use std::env;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
+use mongodb::bson::doc;
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed, UserId},
+    ChoiceParameter, CreateReply,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use sha256::digest;
+
+use super::CommandContext;
+use crate::{
+    models::{DBCollective, DBMate, DBMateTombstone},
+    utils::{
+        collectives::{get_or_create_collective, get_owner_id},
+        front::{calculate_front_stats, switched_in_since},
+        mates::{get_mate_names, get_switched_in_mate},
+        time::format_duration,
+    },
+};
+
+#[poise::command(slash_command, subcommands("stats", "api"))]
+pub async fn front(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// See who's currently switched in for a collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn fronters(
+    ctx: CommandContext<'_>,
+    #[description = "the user whose fronters you want to see (defaults to you if unspecified)"]
+    user: Option<serenity::User>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let user_id = get_owner_id(
+        &collectives_collection,
+        user.map(|user| user.id).unwrap_or(ctx.author().id),
+    )
+    .await?;
+    let is_own = user_id == get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+
+    if !collective.is_public && !is_own {
+        bail!("That user's collective is private!");
+    }
+
+    if !collective.privacy_settings().front.allows(is_own) {
+        bail!("That collective's fronters are private!");
+    }
+
+    let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    let Some(mate) = mate.filter(|mate| mate.is_public || is_own) else {
+        ctx.say("Nobody is currently switched in!").await?;
+
+        return Ok(());
+    };
+
+    let privacy = mate.privacy_settings();
+
+    let mut embed = CreateEmbed::new().title(mate.display_name.unwrap_or(mate.name));
+
+    if privacy.avatar.allows(is_own) {
+        embed = embed.thumbnail(mate.avatar);
+    }
+
+    if let Some(pronouns) = mate.pronouns.filter(|_| privacy.pronouns.allows(is_own)) {
+        embed = embed.field("Pronouns", pronouns, false);
+    }
+
+    if let Some(since) = switched_in_since(&collective) {
+        embed = embed.field("Switched in", format!("<t:{}:R>", since.timestamp()), false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Turn the public JSON endpoint for your current fronters on or off (eg for stream overlays)
+#[poise::command(slash_command, ephemeral)]
+pub async fn api(
+    ctx: CommandContext<'_>,
+    #[description = "whether the endpoint should be enabled (generates a new token if so)"]
+    enabled: bool,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if !enabled {
+        collectives_collection
+            .update_one(
+                doc! { "user_id": collective.user_id },
+                doc! { "$unset": { "front_api_token": "" } },
+                None,
+            )
+            .await?;
+
+        ctx.say("Disabled your fronters endpoint!").await?;
+
+        return Ok(());
+    }
+
+    // without it there's no link to give out, so there's no point making a token
+    let api_url = env::var("PUBLIC_API_URL").context(
+        "The fronters endpoint isn't available here, since this instance of Multiplex doesn't \
+         have a public URL for it!",
+    )?;
+
+    let token = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$set": { "front_api_token": digest(token.clone()) } },
+            None,
+        )
+        .await?;
+
+    let path = format!("/fronters/{}?token={}", collective.user_id, token);
+
+    ctx.say(format!(
+        "Enabled your fronters endpoint! Anyone with this link can see who's switched in, so keep \
+         it secret (running this again makes a new one): `{}{}`",
+        api_url.trim_end_matches('/'),
+        path
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, ChoiceParameter, PartialEq)]
+pub enum FrontStatsWindow {
+    #[name = "The last day"]
+    Day,
+    #[name = "The last week"]
+    Week,
+    #[name = "The last month"]
+    Month,
+    #[name = "A custom number of days"]
+    Custom,
+}
+
+const BAR_WIDTH: usize = 20;
+
+/// Shows how long each of your mates has fronted
+#[poise::command(slash_command, ephemeral)]
+pub async fn stats(
+    ctx: CommandContext<'_>,
+    #[description = "the window to calculate front time over (defaults to the last week)"]
+    window: Option<FrontStatsWindow>,
+    #[description = "the number of days to look back over (only used with a custom window)"]
+    #[min = 1]
+    #[max = 365]
+    days: Option<u32>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+
+    let window = window.unwrap_or(FrontStatsWindow::Week);
+
+    let (window_length, window_name) = match window {
+        FrontStatsWindow::Day => (Duration::days(1), "the last day".to_string()),
+        FrontStatsWindow::Week => (Duration::weeks(1), "the last week".to_string()),
+        FrontStatsWindow::Month => (Duration::days(30), "the last month".to_string()),
+        FrontStatsWindow::Custom => {
+            let days = days.context("You need to specify a number of days for a custom window!")?;
+
+            (
+                Duration::days(days as i64),
+                format!("the last {} day{}", days, if days == 1 { "" } else { "s" }),
+            )
+        }
+    };
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let end = Utc::now();
+    let start = end - window_length;
+
+    let stats = calculate_front_stats(&collective.switch_logs.unwrap_or_default(), start, end);
+
+    if stats.is_empty() {
+        ctx.say("You haven't logged any switches in that time!")
+            .await?;
+
+        return Ok(());
+    }
+
+    let mate_names = get_mate_names(
+        &mates_collection,
+        &tombstones_collection,
+        UserId::new(collective.user_id as u64),
+    )
+    .await?;
+
+    let names = stats
+        .iter()
+        .map(|stat| match stat.mate_id {
+            Some(mate_id) => mate_names
+                .get(&mate_id)
+                .cloned()
+                .unwrap_or("Unknown mate".to_string()),
+            None => "Nobody".to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    let name_width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .min(16);
+
+    let mut chart = vec![];
+    let mut fields = vec![];
+
+    // embeds can only have 25 fields
+    for (stat, name) in stats.iter().zip(names.iter()).take(25) {
+        let percentage =
+            stat.total.num_seconds() as f64 / window_length.num_seconds() as f64 * 100.0;
+        let filled = ((percentage / 100.0) * BAR_WIDTH as f64).round() as usize;
+
+        chart.push(format!(
+            "{:<width$} {}{} {:>5.1}%",
+            name.chars().take(name_width).collect::<String>(),
+            "█".repeat(filled.min(BAR_WIDTH)),
+            "░".repeat(BAR_WIDTH - filled.min(BAR_WIDTH)),
+            percentage,
+            width = name_width
+        ));
+
+        fields.push((
+            name.clone(),
+            format!(
+                "{} ({:.1}%)\nLongest stretch: {}",
+                format_duration(stat.total),
+                percentage,
+                format_duration(stat.longest)
+            ),
+            true,
+        ));
+    }
+
+    let embed = CreateEmbed::new()
+        .title(format!("Front time over {}", window_name))
+        .description(format!("```\n{}\n```", chart.join("\n")))
+        .fields(fields);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
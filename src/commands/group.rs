@@ -0,0 +1,283 @@
+use anyhow::{bail, Context, Result};
+use mongodb::bson::doc;
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed},
+    CreateReply,
+};
+
+use super::{
+    autocomplete::{group as group_autocomplete, mate as mate_autocomplete},
+    CommandContext,
+};
+use crate::{
+    models::{DBCollective, DBGroup, DBMate},
+    utils::{
+        avatars::upload_avatar,
+        collectives::{get_or_create_collective, get_owner_id},
+        groups::get_group,
+        mates::{get_all_mates, get_mate},
+    },
+};
+
+#[poise::command(
+    slash_command,
+    subcommands("create", "add", "remove", "info", "delete")
+)]
+pub async fn group(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Create a new group to organise your mates
+#[poise::command(slash_command, ephemeral)]
+pub async fn create(
+    ctx: CommandContext<'_>,
+    #[description = "the name of the group"] name: String,
+    #[description = "what the group is for"] description: Option<String>,
+    #[description = "an (optional) icon for the group"] icon: Option<serenity::Attachment>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    if get_group(&groups_collection, user_id, name.clone())
+        .await
+        .is_some()
+    {
+        bail!("You cannot have more than one group with the same name!");
+    }
+
+    let icon = if let Some(icon) = icon {
+        Some(upload_avatar(ctx.data().avatar_storage.as_ref(), icon).await?)
+    } else {
+        None
+    };
+
+    groups_collection
+        .insert_one(
+            DBGroup {
+                id: None,
+                user_id: user_id.get() as i64,
+                name: name.clone(),
+                description,
+                icon,
+                mate_ids: vec![],
+            },
+            None,
+        )
+        .await
+        .context("Failed to create group; try again later!")?;
+
+    ctx.say(format!("Created group {}!", name)).await?;
+
+    Ok(())
+}
+
+/// Add a mate to a group
+#[poise::command(slash_command, ephemeral)]
+pub async fn add(
+    ctx: CommandContext<'_>,
+    #[description = "the group to add the mate to"]
+    #[autocomplete = "group_autocomplete"]
+    group: String,
+    #[description = "the mate to add"]
+    #[autocomplete = "mate_autocomplete"]
+    mate: String,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let group = get_group(&groups_collection, user_id, group)
+        .await
+        .context("Failed to find group; does it exist?")?;
+
+    let mate = get_mate(&mates_collection, user_id, mate)
+        .await
+        .context("Failed to find mate; do they actually exist?")?;
+
+    groups_collection
+        .update_one(
+            doc! { "_id": group.id },
+            doc! { "$addToSet": { "mate_ids": mate.id } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!("Added {} to {}!", mate.name, group.name))
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a mate from a group
+#[poise::command(slash_command, ephemeral)]
+pub async fn remove(
+    ctx: CommandContext<'_>,
+    #[description = "the group to remove the mate from"]
+    #[autocomplete = "group_autocomplete"]
+    group: String,
+    #[description = "the mate to remove"]
+    #[autocomplete = "mate_autocomplete"]
+    mate: String,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let group = get_group(&groups_collection, user_id, group)
+        .await
+        .context("Failed to find group; does it exist?")?;
+
+    let mate = get_mate(&mates_collection, user_id, mate)
+        .await
+        .context("Failed to find mate; do they actually exist?")?;
+
+    if !mate
+        .id
+        .is_some_and(|mate_id| group.mate_ids.contains(&mate_id))
+    {
+        bail!("{} isn't in {}!", mate.name, group.name);
+    }
+
+    groups_collection
+        .update_one(
+            doc! { "_id": group.id },
+            doc! { "$pull": { "mate_ids": mate.id } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!("Removed {} from {}!", mate.name, group.name))
+        .await?;
+
+    Ok(())
+}
+
+/// Get the info of one of a user's groups
+#[poise::command(slash_command, ephemeral)]
+pub async fn info(
+    ctx: CommandContext<'_>,
+    #[description = "the name of the group"]
+    #[autocomplete = "group_autocomplete"]
+    group: String,
+    #[description = "the user whose group you want to see (defaults to you if unspecified)"]
+    user: Option<serenity::User>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(
+        &collectives_collection,
+        user.map(|user| user.id).unwrap_or(ctx.author().id),
+    )
+    .await?;
+    let is_own = user_id == get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+
+    if !collective.privacy_settings().group_list.allows(is_own) {
+        bail!("That collective's groups are private!");
+    }
+
+    let group = get_group(&groups_collection, user_id, group)
+        .await
+        .context("Failed to find group; does it exist?")?;
+
+    let members = get_all_mates(&mates_collection, user_id)
+        .await?
+        .into_iter()
+        .filter(|mate| {
+            mate.id
+                .is_some_and(|mate_id| group.mate_ids.contains(&mate_id))
+        })
+        .filter(|mate| mate.is_public || is_own)
+        .map(|mate| match mate.display_name.clone() {
+            Some(display_name) if mate.privacy_settings().name.allows(is_own) => format!(
+                "{} *({})*",
+                display_name.replace('*', "\\*"),
+                mate.name.replace('*', "\\*")
+            ),
+            Some(display_name) => display_name.replace('*', "\\*"),
+            None => mate.name.replace('*', "\\*"),
+        })
+        .collect::<Vec<_>>();
+
+    let mut embed = CreateEmbed::new().title(group.name);
+
+    if let Some(icon) = group.icon {
+        embed = embed.thumbnail(icon);
+    }
+
+    if let Some(description) = group.description {
+        embed = embed.field("Description", description, false);
+    }
+
+    embed = embed.field(
+        format!("Members ({})", members.len()),
+        if members.is_empty() {
+            "Nobody yet!".to_string()
+        } else {
+            // field values can only be 1024 characters long
+            let mut value = String::new();
+
+            for member in members.iter() {
+                if value.len() + member.len() + 5 > 1024 {
+                    value.push_str("\n...");
+                    break;
+                }
+
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(member);
+            }
+
+            value
+        },
+        false,
+    );
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Delete a group (its mates aren't deleted)
+#[poise::command(slash_command, ephemeral)]
+pub async fn delete(
+    ctx: CommandContext<'_>,
+    #[description = "the group to delete"]
+    #[autocomplete = "group_autocomplete"]
+    group: String,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let result = groups_collection
+        .delete_one(
+            doc! { "user_id": user_id.get() as i64, "name": group.clone() },
+            None,
+        )
+        .await?;
+
+    if result.deleted_count == 0 {
+        bail!("Failed to find group; does it exist?");
+    }
+
+    ctx.say(format!("Deleted group {}!", group)).await?;
+
+    Ok(())
+}
@@ -1,147 +1,525 @@
+use std::time::Duration;
+
 use anyhow::Result;
-use mongodb::bson::{self, doc};
-use poise::serenity_prelude::{self};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use poise::{
+    serenity_prelude::{
+        self, futures::StreamExt, ButtonStyle, ComponentInteractionCollector, CreateActionRow,
+        CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+        UserId,
+    },
+    CreateReply,
+};
 
 use super::CommandContext;
 use crate::{
     commands::UPSERT_OPTIONS,
-    models::{DBCollective, DBMate},
+    models::{
+        DBCollective, DBGroup, DBMate, DBMateTombstone, DBTrashedMate, DBUserSettings, SwitchLog,
+    },
+    multiplex::MultiplexExport,
     pluralkit::PluralkitExport,
     tupperbox::TupperboxExport,
+    utils::{
+        avatars::{describe_rehost_failures, rehost_mate_avatars},
+        backups::create_backup,
+        collectives::{
+            get_or_create_collective, get_owner_id, merge_switch_logs, request_links,
+            save_switch_logs,
+        },
+        front::handle_front_change,
+        groups::{insert_missing_groups, upsert_groups},
+        imports::{
+            apply_mate_plans, describe_outcome, imported_mates, plan_collective, plan_mates,
+            upsert_by_keys, ImportPreview, MergeStrategy,
+        },
+        mates::{get_all_mates, get_switched_in_mate, sync_switched_in_mate},
+    },
 };
 
-#[poise::command(slash_command, subcommands("tupperbox", "pluralkit"))]
+#[poise::command(slash_command, subcommands("tupperbox", "pluralkit", "multiplex"))]
 pub async fn import(_ctx: CommandContext<'_>) -> Result<()> {
     // This can't be reached without a prefix command, which we don't use
     unreachable!()
 }
 
-// FIXME: upsertion of mates should be converted to a util at some point in the future
+/// Shows what an import is going to do, and waits for it to be confirmed (the same way `/reset`
+/// does). Nothing should be written until this returns true
+async fn confirm_import(ctx: CommandContext<'_>, preview: CreateEmbed) -> Result<bool> {
+    let confirm_id = format!("{}confirm", ctx.id());
+    let cancel_id = format!("{}cancel", ctx.id());
 
-/// Import a Tupperbox export into your collective (WARNING: WILL OVERWRITE CURRENT COLLECTIVE MEMBERS)
-#[poise::command(slash_command, ephemeral)]
-pub async fn tupperbox(
-    ctx: CommandContext<'_>,
-    #[description = "your tupperbox export"] file: serenity_prelude::Attachment,
-) -> Result<()> {
-    let download = file.download().await?;
-    let content = String::from_utf8_lossy(&download);
+    let reply = CreateReply::default()
+        .embed(preview)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(confirm_id.clone())
+                .label("Import")
+                .style(ButtonStyle::Success),
+            CreateButton::new(cancel_id.clone())
+                .label("Cancel")
+                .style(ButtonStyle::Secondary),
+        ])]);
 
-    let export: TupperboxExport = serde_json::from_str(&content)?;
+    let handle = ctx.send(reply).await?;
 
-    let database = &ctx.data().database;
+    let ctx_id = ctx.id();
+    let mut collector = ComponentInteractionCollector::new(&ctx.serenity_context().shard)
+        .timeout(Duration::from_secs(120))
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .stream();
 
-    let mates_collection = database.collection::<DBMate>("mates");
+    while let Some(press) = collector.next().await {
+        let confirmed = press.data.custom_id == confirm_id;
 
-    for tupper in export.tuppers.iter() {
-        let mate = tupper.to_mate(ctx.author().id)?;
+        if !confirmed && press.data.custom_id != cancel_id {
+            continue;
+        }
 
-        mates_collection
-            .find_one_and_update(
-                doc! { "user_id": ctx.author().id.get() as i64, "name": mate.name.clone() },
-                doc! { "$set": bson::to_bson(&mate).unwrap() },
-                UPSERT_OPTIONS.clone().unwrap(),
+        press
+            .create_response(
+                ctx.http(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::default()
+                        .content(if confirmed {
+                            "Importing..."
+                        } else {
+                            "Import cancelled; nothing was changed."
+                        })
+                        .components(vec![]),
+                ),
             )
             .await?;
+
+        return Ok(confirmed);
     }
 
-    ctx.say(
-        "Successfully imported Tupperbox export! (Note: exported Tuppers may have taken \
-         precedence over any existing mates. If so, sorry! They're unrecoverable!)",
-    )
-    .await?;
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Import timed out; nothing was changed.")
+                .components(vec![]),
+        )
+        .await?;
 
-    Ok(())
+    Ok(false)
 }
 
-/// Import a Multiplex export into your collective (WARNING: WILL OVERWRITE ALL COLLECTIVE INFORMATION)
-#[poise::command(slash_command, ephemeral)]
-pub async fn multiplex(
-    ctx: CommandContext<'_>,
-    #[description = "your multiplex export"] file: serenity_prelude::Attachment,
-) -> Result<()> {
-    let download = file.download().await?;
-    let content = String::from_utf8_lossy(&download);
+/// Where an import's mates, groups and switches come from, so every kind of export goes through
+/// the same preview, backup and merge
+#[async_trait]
+trait Export: Sync {
+    /// what the export's called in replies, ie `PluralKit`
+    const SOURCE: &'static str;
 
-    let export: PluralkitExport = serde_json::from_str(&content)?;
+    fn collective(&self, user_id: UserId) -> Result<Option<DBCollective>>;
+
+    fn mates(&self, user_id: UserId) -> Result<Vec<DBMate>>;
+
+    /// made once the mates have been imported, since they point at their ids
+    fn groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup>;
+
+    /// made once the mates have been imported, since they point at their ids
+    fn switch_logs(&self, mates: &[DBMate]) -> Vec<SwitchLog>;
+
+    /// when each of the export's switches happened, for the preview
+    fn switch_dates(&self) -> Vec<DateTime<Utc>>;
+
+    /// other accounts to ask to link back up
+    fn accounts(&self) -> Vec<i64>;
+
+    fn dropped(&self) -> Vec<String>;
+
+    fn notes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// anything else the export brings along, written after everything else
+    async fn import_extras(
+        &self,
+        _ctx: CommandContext<'_>,
+        _user_id: UserId,
+        _strategy: MergeStrategy,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Export for TupperboxExport {
+    const SOURCE: &'static str = "Tupperbox";
+
+    fn collective(&self, _user_id: UserId) -> Result<Option<DBCollective>> {
+        Ok(None)
+    }
+
+    fn mates(&self, user_id: UserId) -> Result<Vec<DBMate>> {
+        self.tuppers
+            .iter()
+            .map(|tupper| tupper.to_mate(user_id))
+            .collect()
+    }
 
+    fn groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        self.to_groups(user_id, mates)
+    }
+
+    fn switch_logs(&self, _mates: &[DBMate]) -> Vec<SwitchLog> {
+        vec![]
+    }
+
+    fn switch_dates(&self) -> Vec<DateTime<Utc>> {
+        vec![]
+    }
+
+    fn accounts(&self) -> Vec<i64> {
+        vec![]
+    }
+
+    fn dropped(&self) -> Vec<String> {
+        TupperboxExport::dropped(self)
+    }
+}
+
+#[async_trait]
+impl Export for PluralkitExport<'_> {
+    const SOURCE: &'static str = "PluralKit";
+
+    fn collective(&self, user_id: UserId) -> Result<Option<DBCollective>> {
+        Ok(Some(self.to_collective(user_id)?))
+    }
+
+    fn mates(&self, user_id: UserId) -> Result<Vec<DBMate>> {
+        self.members
+            .iter()
+            .map(|member| member.to_mate(user_id))
+            .collect()
+    }
+
+    fn groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        self.to_groups(user_id, mates)
+    }
+
+    fn switch_logs(&self, mates: &[DBMate]) -> Vec<SwitchLog> {
+        self.to_switch_logs(mates)
+    }
+
+    fn switch_dates(&self) -> Vec<DateTime<Utc>> {
+        self.switches
+            .iter()
+            .map(|switch| switch.timestamp)
+            .collect()
+    }
+
+    fn accounts(&self) -> Vec<i64> {
+        self.accounts.clone()
+    }
+
+    fn dropped(&self) -> Vec<String> {
+        PluralkitExport::dropped(self)
+    }
+}
+
+#[async_trait]
+impl Export for MultiplexExport {
+    const SOURCE: &'static str = "Multiplex";
+
+    fn collective(&self, user_id: UserId) -> Result<Option<DBCollective>> {
+        Ok(Some(self.to_collective(user_id)))
+    }
+
+    fn mates(&self, user_id: UserId) -> Result<Vec<DBMate>> {
+        Ok(self.to_mates(user_id))
+    }
+
+    fn groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        self.to_groups(user_id, mates)
+    }
+
+    fn switch_logs(&self, mates: &[DBMate]) -> Vec<SwitchLog> {
+        self.to_switch_logs(mates)
+    }
+
+    fn switch_dates(&self) -> Vec<DateTime<Utc>> {
+        self.collective
+            .switch_logs
+            .iter()
+            .flatten()
+            .map(|log| log.date)
+            .collect()
+    }
+
+    fn accounts(&self) -> Vec<i64> {
+        self.collective.accounts()
+    }
+
+    fn dropped(&self) -> Vec<String> {
+        MultiplexExport::dropped(self)
+    }
+
+    fn notes(&self) -> Vec<String> {
+        [
+            (self.tombstones.len(), "deleted mates"),
+            (self.trash.len(), "mates in the trash"),
+            (self.settings.len(), "settings (global and per server)"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {} will be imported", count, what))
+        .collect()
+    }
+
+    async fn import_extras(
+        &self,
+        ctx: CommandContext<'_>,
+        user_id: UserId,
+        strategy: MergeStrategy,
+    ) -> Result<()> {
+        let database = &ctx.data().database;
+
+        // these are never overwritten, since they're looked up by ids that could belong to
+        // someone else
+        upsert_by_keys(
+            &database.collection::<DBMateTombstone>("tombstones"),
+            &["_id"],
+            &self.to_tombstones(user_id),
+            false,
+        )
+        .await?;
+        upsert_by_keys(
+            &database.collection::<DBTrashedMate>("trash"),
+            &["_id"],
+            &self.to_trash(user_id),
+            false,
+        )
+        .await?;
+
+        // settings belong to whoever's importing, not the collective's owner
+        upsert_by_keys(
+            &database.collection::<DBUserSettings>("settings"),
+            &["user_id", "guild_id"],
+            &self.to_settings(ctx.author().id),
+            strategy == MergeStrategy::Overwrite,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Previews an import, then (once it's confirmed and backed up) merges it into the collective
+async fn run_import<E: Export>(
+    ctx: CommandContext<'_>,
+    export: E,
+    rehost_avatars: bool,
+    strategy: MergeStrategy,
+) -> Result<()> {
     let database = &ctx.data().database;
 
     let collectives_collection = database.collection::<DBCollective>("collectives");
     let mates_collection = database.collection::<DBMate>("mates");
+    let groups_collection = database.collection::<DBGroup>("groups");
 
-    collectives_collection
-        .find_one_and_update(
-            doc! { "user_id": ctx.author().id.get() as i64 },
-            doc! { "$set": bson::to_bson(&export.to_collective(ctx.author().id)?).unwrap() },
-            UPSERT_OPTIONS.clone().unwrap(),
-        )
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = export.collective(user_id)?;
+    let mut mates = export.mates(user_id)?;
+
+    let existing_collective = collectives_collection
+        .find_one(doc! { "user_id": user_id.get() as i64 }, None)
         .await?;
+    let existing_mates = get_all_mates(&mates_collection, user_id).await?;
+
+    let collective_update = collective
+        .map(|collective| plan_collective(existing_collective.as_ref(), &collective, strategy))
+        .transpose()?
+        .flatten();
 
-    for member in export.members.iter() {
-        let mate = member.to_mate(ctx.author().id)?;
+    let existing_switch_logs = existing_collective
+        .as_ref()
+        .and_then(|collective| collective.switch_logs.clone())
+        .unwrap_or_default();
+    let imported_switch_dates = export.switch_dates();
 
-        mates_collection
+    let mut preview = ImportPreview::default()
+        .with_collective(existing_collective.as_ref(), collective_update.as_ref())
+        .with_mates(
+            &plan_mates(&existing_mates, &mates, strategy)?,
+            rehost_avatars,
+        )
+        .with_switch_logs(&existing_switch_logs, &imported_switch_dates, strategy)
+        .with_dropped(export.dropped());
+
+    for note in export.notes() {
+        preview = preview.with_note(note);
+    }
+
+    if !confirm_import(ctx, preview.to_embed(E::SOURCE)).await? {
+        return Ok(());
+    }
+
+    create_backup(
+        database,
+        user_id,
+        &format!("before /import {}", E::SOURCE.to_lowercase()),
+    )
+    .await?;
+
+    if let Some(collective_update) = collective_update {
+        collectives_collection
             .find_one_and_update(
-                doc! { "user_id": ctx.author().id.get() as i64, "name": mate.name.clone() },
-                doc! { "$set": bson::to_bson(&mate).unwrap() },
+                doc! { "user_id": user_id.get() as i64 },
+                doc! { "$set": collective_update },
                 UPSERT_OPTIONS.clone().unwrap(),
             )
             .await?;
     }
 
-    ctx.say(
-        "Successfully imported Multiplex export! (Note: exported Members may have taken \
-         precedence over any existing mates, and your collective information was completely \
-         overwritten with whatever you gave us. If so, sorry! They're unrecoverable!)",
-    )
+    let rehost_failures = if rehost_avatars {
+        rehost_mate_avatars(ctx.data().avatar_storage.as_ref(), &mut mates).await
+    } else {
+        vec![]
+    };
+
+    // planned again, now the avatars have moved
+    let plans = plan_mates(&existing_mates, &mates, strategy)?;
+    apply_mate_plans(&mates_collection, user_id, &plans).await?;
+
+    let mates = imported_mates(&get_all_mates(&mates_collection, user_id).await?, &plans);
+    let groups = export.groups(user_id, &mates);
+
+    if strategy == MergeStrategy::Overwrite {
+        upsert_groups(&groups_collection, &groups).await?;
+    } else {
+        insert_missing_groups(&groups_collection, user_id, &groups).await?;
+    }
+
+    if !imported_switch_dates.is_empty() {
+        let switch_logs = merge_switch_logs(
+            &existing_switch_logs,
+            &export.switch_logs(&mates),
+            strategy.prefers_incoming(),
+        );
+
+        let previous_mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+        save_switch_logs(&collectives_collection, user_id.get() as i64, &switch_logs).await?;
+        sync_switched_in_mate(&mates_collection, user_id, &switch_logs).await?;
+
+        let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+        let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+
+        handle_front_change(ctx.http(), database, &collective, previous_mate, mate).await?;
+    }
+
+    export.import_extras(ctx, user_id, strategy).await?;
+
+    let accounts = export.accounts();
+
+    if !accounts.is_empty() {
+        let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+        request_links(&collectives_collection, &collective, &accounts).await?;
+    }
+
+    ctx.say(format!(
+        "Successfully imported {} export! (If anything went wrong, `/backup restore` can undo \
+         it.{}){}{}",
+        E::SOURCE,
+        if accounts.is_empty() {
+            ""
+        } else {
+            " Any other accounts in the export can run `/link accept` to link back up."
+        },
+        describe_outcome(&plans),
+        describe_rehost_failures(&rehost_failures)
+    ))
     .await?;
 
     Ok(())
 }
 
-/// Import a Pluralkit export into your collective (WARNING: WILL OVERWRITE ALL COLLECTIVE INFORMATION)
+/// Import a Tupperbox export into your collective (a backup is made first; see /backup list)
 #[poise::command(slash_command, ephemeral)]
-pub async fn pluralkit(
+pub async fn tupperbox(
     ctx: CommandContext<'_>,
-    #[description = "your pluralkit export"] file: serenity_prelude::Attachment,
+    #[description = "your tupperbox export"] file: serenity_prelude::Attachment,
+    #[description = "whether to copy avatars over to Multiplex so they can't expire (defaults to \
+                     true)"]
+    rehost_avatars: Option<bool>,
+    #[description = "what to do with mates that are already there (defaults to overwriting them)"]
+    strategy: Option<MergeStrategy>,
 ) -> Result<()> {
+    // copying avatars over can take a while
+    ctx.defer_ephemeral().await?;
+
     let download = file.download().await?;
     let content = String::from_utf8_lossy(&download);
 
-    let export: PluralkitExport = serde_json::from_str(&content)?;
-
-    let database = &ctx.data().database;
+    let export: TupperboxExport = serde_json::from_str(&content)?;
 
-    let collectives_collection = database.collection::<DBCollective>("collectives");
-    let mates_collection = database.collection::<DBMate>("mates");
+    run_import(
+        ctx,
+        export,
+        rehost_avatars.unwrap_or(true),
+        strategy.unwrap_or(MergeStrategy::Overwrite),
+    )
+    .await
+}
 
-    collectives_collection
-        .find_one_and_update(
-            doc! { "user_id": ctx.author().id.get() as i64 },
-            doc! { "$set": bson::to_bson(&export.to_collective(ctx.author().id)?).unwrap() },
-            UPSERT_OPTIONS.clone().unwrap(),
-        )
-        .await?;
+/// Import a Multiplex export into your collective (a backup is made first; see /backup list)
+#[poise::command(slash_command, ephemeral)]
+pub async fn multiplex(
+    ctx: CommandContext<'_>,
+    #[description = "your multiplex export"] file: serenity_prelude::Attachment,
+    #[description = "whether to copy avatars over to Multiplex so they can't expire (defaults to \
+                     true)"]
+    rehost_avatars: Option<bool>,
+    #[description = "what to do with mates that are already there (defaults to overwriting them)"]
+    strategy: Option<MergeStrategy>,
+) -> Result<()> {
+    // copying avatars over can take a while
+    ctx.defer_ephemeral().await?;
 
-    for member in export.members.iter() {
-        let mate = member.to_mate(ctx.author().id)?;
+    let download = file.download().await?;
+    let content = String::from_utf8_lossy(&download);
 
-        mates_collection
-            .find_one_and_update(
-                doc! { "user_id": ctx.author().id.get() as i64, "name": mate.name.clone() },
-                doc! { "$set": bson::to_bson(&mate).unwrap() },
-                UPSERT_OPTIONS.clone().unwrap(),
-            )
-            .await?;
-    }
+    let export = MultiplexExport::parse(&content)?;
 
-    ctx.say(
-        "Successfully imported Pluralkit export! (Note: exported Members may have taken \
-         precedence over any existing mates, and your collective information was completely \
-         overwritten with whatever PK gave us. If so, sorry! They're unrecoverable!)",
+    run_import(
+        ctx,
+        export,
+        rehost_avatars.unwrap_or(true),
+        strategy.unwrap_or(MergeStrategy::Overwrite),
     )
-    .await?;
+    .await
+}
 
-    Ok(())
+/// Import a Pluralkit export into your collective (a backup is made first; see /backup list)
+#[poise::command(slash_command, ephemeral)]
+pub async fn pluralkit(
+    ctx: CommandContext<'_>,
+    #[description = "your pluralkit export"] file: serenity_prelude::Attachment,
+    #[description = "whether to copy avatars over to Multiplex so they can't expire (defaults to \
+                     true)"]
+    rehost_avatars: Option<bool>,
+    #[description = "what to do with mates that are already there (defaults to overwriting them)"]
+    strategy: Option<MergeStrategy>,
+) -> Result<()> {
+    // copying avatars over can take a while
+    ctx.defer_ephemeral().await?;
+
+    let download = file.download().await?;
+    let content = String::from_utf8_lossy(&download);
+
+    let export: PluralkitExport = serde_json::from_str(&content)?;
+
+    run_import(
+        ctx,
+        export,
+        rehost_avatars.unwrap_or(true),
+        strategy.unwrap_or(MergeStrategy::Overwrite),
+    )
+    .await
 }
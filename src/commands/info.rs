@@ -11,12 +11,15 @@ use poise::{
     CreateReply,
 };
 
-use super::CommandContext;
+use super::{autocomplete::group as group_autocomplete, CommandContext};
 use crate::{
-    models::{DBCollective, DBMate},
+    models::{DBCollective, DBGroup, DBMate},
     utils::{
-        collectives::get_or_create_collective,
+        collectives::{get_or_create_collective, get_owner_id},
+        groups::get_group,
         mates::{get_all_mates, get_mate},
+        misc::color_to_embed,
+        time::format_birthday,
     },
 };
 
@@ -30,9 +33,13 @@ pub async fn info(
     #[description = "the name of the mate you want to get information about (if unspecified, gets \
                      collective information)"]
     mate: Option<String>,
+    #[description = "only list the mates in this group (when getting collective information)"]
+    #[autocomplete = "group_autocomplete"]
+    group: Option<String>,
 ) -> Result<()> {
     let database = &ctx.data().database;
     let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
 
     let user_id;
     if user.is_none() {
@@ -40,26 +47,45 @@ pub async fn info(
     } else {
         user_id = user.clone().unwrap().id.get() as i64;
     }
-    let user_id = UserId::new(user_id as u64);
+    let user = ctx.http().get_user(UserId::new(user_id as u64)).await?;
+
+    // linked accounts all share the collective of whichever account owns it
+    let user_id = get_owner_id(&collectives_collection, user.id).await?;
+    let author_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
 
     if let Some(mate) = mate {
         let mate = get_mate(&mates_collection, user_id, mate.clone())
             .await
             .context("")?;
 
-        if !mate.is_public && ctx.author().id != user_id {
+        let is_own = author_id == user_id;
+
+        if !mate.is_public && !is_own {
             bail!("That mate doesn't exist!");
         }
 
-        let mut final_embed = CreateEmbed::new()
-            .title(mate.display_name.unwrap_or(mate.name))
-            .thumbnail(mate.avatar);
-        if let Some(bio) = mate.bio {
+        let privacy = mate.privacy_settings();
+
+        let mut final_embed = CreateEmbed::new().title(mate.display_name.unwrap_or(mate.name));
+        if let Some(color) = mate.color.as_deref().and_then(color_to_embed) {
+            final_embed = final_embed.colour(color);
+        }
+        if privacy.avatar.allows(is_own) {
+            final_embed = final_embed.thumbnail(mate.avatar);
+
+            if let Some(banner) = mate.banner {
+                final_embed = final_embed.image(banner);
+            }
+        }
+        if let Some(bio) = mate.bio.filter(|_| privacy.description.allows(is_own)) {
             final_embed = final_embed.field("Bio", bio, false);
         }
-        if let Some(pronouns) = mate.pronouns {
+        if let Some(pronouns) = mate.pronouns.filter(|_| privacy.pronouns.allows(is_own)) {
             final_embed = final_embed.field("Pronouns", pronouns, false);
         }
+        if let Some(birthday) = mate.birthday.filter(|_| privacy.birthday.allows(is_own)) {
+            final_embed = final_embed.field("Birthday", format_birthday(birthday), false);
+        }
         if mate.prefix.is_some() || mate.postfix.is_some() {
             final_embed = final_embed.field(
                 "Selector",
@@ -74,11 +100,11 @@ pub async fn info(
 
         ctx.send(CreateReply::default().embed(final_embed)).await?;
     } else {
-        let user = ctx.http().get_user(user_id).await?;
-
-        let collectives_collection = database.collection::<DBCollective>("collectives");
         let collective = get_or_create_collective(&collectives_collection, user_id).await?;
-        let mates = get_all_mates(&mates_collection, user_id).await?;
+        let mut mates = get_all_mates(&mates_collection, user_id).await?;
+
+        let is_own = author_id == user_id;
+        let privacy = collective.privacy_settings();
 
         let ctx_id = ctx.id();
         let prev_button_id = format!("{}prev", ctx_id);
@@ -86,30 +112,100 @@ pub async fn info(
 
         let mut current_page = 0;
 
-        let mut final_embed = CreateEmbed::new().title(collective.name.unwrap_or(format!(
-            "{}'s Collective",
-            user.global_name.clone().unwrap_or(user.name.clone())
-        )));
+        let mut final_embed = CreateEmbed::new().title(
+            collective
+                .name
+                .filter(|_| privacy.name.allows(is_own))
+                .unwrap_or(format!(
+                    "{}'s Collective",
+                    user.global_name.clone().unwrap_or(user.name.clone())
+                )),
+        );
+
+        if let Some(color) = collective.color.as_deref().and_then(color_to_embed) {
+            final_embed = final_embed.colour(color);
+        }
+
+        if let Some(banner) = collective.banner.filter(|_| privacy.avatar.allows(is_own)) {
+            final_embed = final_embed.image(banner);
+        }
 
-        if let Some(bio) = collective.bio {
+        if let Some(bio) = collective
+            .bio
+            .filter(|_| privacy.description.allows(is_own))
+        {
             final_embed = final_embed.field("Bio", bio, false);
         }
 
-        if let Some(pronouns) = collective.pronouns {
+        if let Some(pronouns) = collective
+            .pronouns
+            .filter(|_| privacy.pronouns.allows(is_own))
+        {
             final_embed = final_embed.field("Pronouns", pronouns, false);
         }
 
+        if let Some(birthday) = collective
+            .birthday
+            .filter(|_| privacy.birthday.allows(is_own))
+        {
+            final_embed = final_embed.field("Birthday", format_birthday(birthday), false);
+        }
+
+        if let Some(id) = collective.id.filter(|_| privacy.metadata.allows(is_own)) {
+            final_embed = final_embed.field(
+                "Created",
+                format!("<t:{}:D>", id.timestamp().timestamp_millis() / 1000),
+                false,
+            );
+        }
+
+        if !privacy.member_list.allows(is_own) {
+            final_embed = final_embed.field("Mates", "This collective's mates are private.", false);
+
+            ctx.send(CreateReply::default().embed(final_embed)).await?;
+
+            return Ok(());
+        }
+
+        if !is_own {
+            mates.retain(|mate| mate.is_public);
+        }
+
+        if let Some(group) = group {
+            if !privacy.group_list.allows(is_own) {
+                bail!("That collective's groups are private!");
+            }
+
+            let groups_collection = database.collection::<DBGroup>("groups");
+
+            let group = get_group(&groups_collection, user_id, group)
+                .await
+                .context("Failed to find group; does it exist?")?;
+
+            mates.retain(|mate| {
+                mate.id
+                    .is_some_and(|mate_id| group.mate_ids.contains(&mate_id))
+            });
+
+            final_embed = final_embed.field("Group", group.name, false);
+        }
+
         let mates_content = mates
             .iter()
             .map(|m| {
-                if let Some(display_name) = m.display_name.clone() {
+                let name_allowed = m.privacy_settings().name.allows(is_own);
+
+                if let Some(display_name) = m.display_name.clone().filter(|_| name_allowed) {
                     format!(
                         "{} *({})*",
                         display_name.replace('*', "\\*"),
                         m.name.replace('*', "\\*")
                     )
                 } else {
-                    m.name.replace('*', "\\*")
+                    m.display_name
+                        .clone()
+                        .unwrap_or(m.name.clone())
+                        .replace('*', "\\*")
                 }
             })
             .collect::<Vec<_>>();
@@ -0,0 +1,240 @@
+use anyhow::{bail, Context, Result};
+use mongodb::{bson::doc, Collection};
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed, UserId},
+    CreateReply,
+};
+
+use super::CommandContext;
+use crate::{
+    models::{DBCollective, DBMate},
+    utils::collectives::{get_or_create_collective, get_owner_id},
+};
+
+#[poise::command(slash_command, subcommands("add", "accept", "remove", "list"))]
+pub async fn link(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Linking would hide an account's own mates, so only accounts without any can be linked
+async fn ensure_linkable(
+    collectives_collection: &Collection<DBCollective>,
+    mates_collection: &Collection<DBMate>,
+    user_id: UserId,
+) -> Result<()> {
+    if get_owner_id(collectives_collection, user_id).await? != user_id {
+        bail!("<@{}> is already linked to a collective!", user_id);
+    }
+
+    let collective = collectives_collection
+        .find_one(doc! { "user_id": user_id.get() as i64 }, None)
+        .await?;
+
+    if collective.is_some_and(|collective| {
+        collective
+            .linked_accounts
+            .is_some_and(|accounts| !accounts.is_empty())
+    }) {
+        bail!("<@{}> already has other accounts linked to it!", user_id);
+    }
+
+    let mate = mates_collection
+        .find_one(doc! { "user_id": user_id.get() as i64 }, None)
+        .await?;
+
+    if mate.is_some() {
+        bail!(
+            "<@{}> already has mates of their own! Export and `/reset` that account first, then \
+             import the export from this one.",
+            user_id
+        );
+    }
+
+    Ok(())
+}
+
+/// Ask another one of your accounts to share this collective (they'll need to `/link accept`)
+#[poise::command(slash_command, ephemeral)]
+pub async fn add(
+    ctx: CommandContext<'_>,
+    #[description = "the account to link to your collective"] user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    if user.bot {
+        bail!("You can't link a bot to your collective!");
+    }
+
+    if get_owner_id(&collectives_collection, ctx.author().id).await? != ctx.author().id {
+        bail!("Only the account that owns your collective can link new accounts!");
+    }
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if collective.accounts().contains(&(user.id.get() as i64)) {
+        bail!("That account is already linked to your collective!");
+    }
+
+    ensure_linkable(&collectives_collection, &mates_collection, user.id).await?;
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$addToSet": { "pending_links": user.id.get() as i64 } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!(
+        "Asked <@{}> to link to your collective! Run `/link accept` on that account to finish \
+         linking.",
+        user.id
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Accept a link request from the account that owns a collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn accept(
+    ctx: CommandContext<'_>,
+    #[description = "the account that asked to link with this one"] user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let author_id = ctx.author().id.get() as i64;
+
+    collectives_collection
+        .find_one(
+            doc! { "user_id": user.id.get() as i64, "pending_links": author_id },
+            None,
+        )
+        .await?
+        .context("That account hasn't asked to link with this one!")?;
+
+    ensure_linkable(&collectives_collection, &mates_collection, ctx.author().id).await?;
+
+    // this account's (empty) collective would otherwise be found before the linked one
+    collectives_collection
+        .delete_one(doc! { "user_id": author_id }, None)
+        .await?;
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": user.id.get() as i64 },
+            doc! {
+                "$pull": { "pending_links": author_id },
+                "$addToSet": { "linked_accounts": author_id }
+            },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!(
+        "Linked this account to <@{}>'s collective! Your mates and switches are now shared.",
+        user.id
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Unlink an account from your collective (or cancel a link request)
+#[poise::command(slash_command, ephemeral)]
+pub async fn remove(
+    ctx: CommandContext<'_>,
+    #[description = "the account to unlink (linked accounts can only unlink themselves)"]
+    user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let is_owner = collective.user_id == ctx.author().id.get() as i64;
+
+    if !is_owner && user.id != ctx.author().id {
+        bail!("Only the account that owns your collective can unlink other accounts!");
+    }
+
+    if collective.user_id == user.id.get() as i64 {
+        bail!("You can't unlink the account that owns the collective!");
+    }
+
+    let account = user.id.get() as i64;
+
+    if !collective.accounts().contains(&account)
+        && !collective
+            .pending_links
+            .unwrap_or_default()
+            .contains(&account)
+    {
+        bail!("That account isn't linked to your collective!");
+    }
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$pull": { "linked_accounts": account, "pending_links": account } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!("Unlinked <@{}> from the collective!", user.id))
+        .await?;
+
+    Ok(())
+}
+
+/// List the accounts linked to your collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn list(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let mention = |account: &i64| format!("<@{}>", account);
+
+    let linked_accounts = collective.linked_accounts.unwrap_or_default();
+    let pending_links = collective.pending_links.unwrap_or_default();
+
+    let mut embed = CreateEmbed::new().title("Linked Accounts").field(
+        "Owner",
+        mention(&collective.user_id),
+        false,
+    );
+
+    if !linked_accounts.is_empty() {
+        embed = embed.field(
+            "Linked",
+            linked_accounts
+                .iter()
+                .map(mention)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false,
+        );
+    }
+
+    if !pending_links.is_empty() {
+        embed = embed.field(
+            "Waiting to accept",
+            pending_links
+                .iter()
+                .map(mention)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use std::{cmp::Reverse, time::Duration};
+
+use anyhow::{bail, Result};
+use poise::{
+    serenity_prelude::{
+        self as serenity, collector::ComponentInteractionCollector, futures::stream::StreamExt,
+        CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, MessageId,
+    },
+    ChoiceParameter, CreateReply,
+};
+
+use super::{autocomplete::similarity, CommandContext};
+use crate::{
+    models::{DBCollective, DBMate, DBMessage},
+    utils::{
+        collectives::{get_or_create_collective, get_owner_id},
+        mates::get_all_mates,
+        messages::{get_mate_message_stats, MateMessageStats},
+    },
+};
+
+/// How close a mate has to be to the search to be listed
+const SEARCH_THRESHOLD: f64 = 0.4;
+
+#[derive(Clone, Debug, ChoiceParameter, PartialEq)]
+pub enum ListSort {
+    #[name = "Name"]
+    Name,
+    #[name = "Creation date"]
+    Created,
+    #[name = "Last proxied"]
+    LastProxied,
+    #[name = "Message count"]
+    MessageCount,
+}
+
+#[derive(Clone, Debug, ChoiceParameter, PartialEq)]
+pub enum ListView {
+    #[name = "Compact (just names)"]
+    Compact,
+    #[name = "Full (everything)"]
+    Full,
+}
+
+/// List (and search through) the mates in a collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn list(
+    ctx: CommandContext<'_>,
+    #[description = "the user whose mates you want to list (defaults to you if unspecified)"]
+    user: Option<serenity::User>,
+    #[description = "only list mates with names like this"] search: Option<String>,
+    #[description = "how to sort the list (defaults to name, or best match when searching)"]
+    sort: Option<ListSort>,
+    #[description = "how much to show about each mate (defaults to compact)"] view: Option<
+        ListView,
+    >,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let messages_collection = database.collection::<DBMessage>("messages");
+
+    let user_id = get_owner_id(
+        &collectives_collection,
+        user.map(|user| user.id).unwrap_or(ctx.author().id),
+    )
+    .await?;
+    let is_own = user_id == get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+
+    if !collective.is_public && !is_own {
+        bail!("That user's collective is private!");
+    }
+
+    if !collective.privacy_settings().member_list.allows(is_own) {
+        bail!("That collective's mates are private!");
+    }
+
+    let message_stats = get_mate_message_stats(&messages_collection, user_id).await?;
+
+    let mut mates = get_all_mates(&mates_collection, user_id)
+        .await?
+        .into_iter()
+        .filter(|mate| mate.is_public || is_own)
+        .map(|mate| {
+            let score = search
+                .as_ref()
+                .map(|search| search_score(&mate, search, is_own))
+                .unwrap_or(1.0);
+
+            (mate, score)
+        })
+        .filter(|(_, score)| *score >= SEARCH_THRESHOLD)
+        .collect::<Vec<_>>();
+
+    if mates.is_empty() {
+        ctx.say(if search.is_some() {
+            "No mates matched that search!"
+        } else {
+            "There aren't any mates to list!"
+        })
+        .await?;
+
+        return Ok(());
+    }
+
+    // don't let sorting give away anything a mate's metadata privacy hides
+    let stats_for = |mate: &DBMate| {
+        message_stats
+            .get(mate)
+            .filter(|_| mate.privacy_settings().metadata.allows(is_own))
+    };
+
+    match sort {
+        None if search.is_some() => {
+            mates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        }
+        None | Some(ListSort::Name) => {
+            mates.sort_by_key(|(mate, _)| mate.name.to_lowercase());
+        }
+        Some(ListSort::Created) => {
+            mates.sort_by_key(|(mate, _)| mate.id);
+        }
+        Some(ListSort::LastProxied) => {
+            mates.sort_by_key(|(mate, _)| {
+                Reverse(stats_for(mate).map(|stats| stats.last_message_id))
+            });
+        }
+        Some(ListSort::MessageCount) => {
+            mates.sort_by_key(|(mate, _)| Reverse(stats_for(mate).map(|stats| stats.count)));
+        }
+    }
+
+    let view = view.unwrap_or(ListView::Compact);
+    let per_page = match view {
+        ListView::Compact => 20,
+        ListView::Full => 5,
+    };
+
+    let pages = mates
+        .chunks(per_page)
+        .enumerate()
+        .map(|(i, page)| {
+            let embed = match view {
+                ListView::Compact => CreateEmbed::new().description(
+                    page.iter()
+                        .map(|(mate, _)| compact_line(mate, is_own))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                ListView::Full => CreateEmbed::new().fields(
+                    page.iter()
+                        .map(|(mate, _)| full_field(mate, stats_for(mate), is_own))
+                        .collect::<Vec<_>>(),
+                ),
+            };
+
+            embed
+                .title(
+                    collective
+                        .name
+                        .clone()
+                        .filter(|_| collective.privacy_settings().name.allows(is_own))
+                        .unwrap_or("Mates".to_string()),
+                )
+                .footer(CreateEmbedFooter::new(format!(
+                    "Page {} of {} ({} mates)",
+                    i + 1,
+                    mates.len().div_ceil(per_page),
+                    mates.len()
+                )))
+        })
+        .collect::<Vec<_>>();
+
+    let ctx_id = ctx.id();
+    let prev_button_id = format!("{}prev", ctx_id);
+    let next_button_id = format!("{}next", ctx_id);
+
+    let mut reply = CreateReply::default().embed(pages[0].clone());
+
+    if pages.len() > 1 {
+        reply = reply.components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id).label("<"),
+            CreateButton::new(&next_button_id).label(">"),
+        ])]);
+    }
+
+    ctx.send(reply).await?;
+
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut current_page = 0;
+
+    let mut collector = ComponentInteractionCollector::new(&ctx.serenity_context().shard)
+        .timeout(Duration::from_secs(300))
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .stream();
+
+    while let Some(press) = collector.next().await {
+        if press.data.custom_id == next_button_id {
+            current_page = (current_page + 1) % pages.len();
+        } else if press.data.custom_id == prev_button_id {
+            current_page = current_page.checked_sub(1).unwrap_or(pages.len() - 1);
+        }
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(pages[current_page].clone()),
+                ),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The best match out of a mate's name and display name; anything containing the search counts
+/// as a perfect match
+fn search_score(mate: &DBMate, search: &str, is_own: bool) -> f64 {
+    let search = search.to_lowercase();
+    let name = listed_name(mate, is_own);
+
+    [Some(&name), mate.display_name.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|name| {
+            let name = name.to_lowercase();
+
+            if name.contains(&search) {
+                1.0
+            } else {
+                similarity(&name, &search)
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+/// The display name if the mate's real name is private, otherwise their real name
+fn listed_name(mate: &DBMate, is_own: bool) -> String {
+    if mate.privacy_settings().name.allows(is_own) {
+        mate.name.clone()
+    } else {
+        mate.display_name.clone().unwrap_or(mate.name.clone())
+    }
+}
+
+fn compact_line(mate: &DBMate, is_own: bool) -> String {
+    let privacy = mate.privacy_settings();
+
+    if let Some(display_name) = mate
+        .display_name
+        .clone()
+        .filter(|_| privacy.name.allows(is_own))
+    {
+        format!(
+            "{} *({})*",
+            display_name.replace('*', "\\*"),
+            mate.name.replace('*', "\\*")
+        )
+    } else {
+        listed_name(mate, is_own).replace('*', "\\*")
+    }
+}
+
+fn full_field(
+    mate: &DBMate,
+    stats: Option<MateMessageStats>,
+    is_own: bool,
+) -> (String, String, bool) {
+    let privacy = mate.privacy_settings();
+    let mut lines = vec![];
+
+    if mate.display_name.is_some() && privacy.name.allows(is_own) {
+        lines.push(format!("Name: {}", mate.name));
+    }
+    if let Some(pronouns) = mate
+        .pronouns
+        .clone()
+        .filter(|_| privacy.pronouns.allows(is_own))
+    {
+        lines.push(format!("Pronouns: {}", pronouns));
+    }
+    if mate.prefix.is_some() || mate.postfix.is_some() {
+        lines.push(format!(
+            "Selector: `{}text{}`",
+            mate.prefix.clone().unwrap_or_default(),
+            mate.postfix.clone().unwrap_or_default()
+        ));
+    }
+
+    if !privacy.metadata.allows(is_own) {
+        return (
+            mate.display_name.clone().unwrap_or(mate.name.clone()),
+            lines.join("\n"),
+            false,
+        );
+    }
+
+    lines.push(format!(
+        "Messages: {}",
+        stats.map(|stats| stats.count).unwrap_or_default()
+    ));
+
+    if let Some(stats) = stats {
+        lines.push(format!(
+            "Last proxied: <t:{}:R>",
+            MessageId::new(stats.last_message_id as u64)
+                .created_at()
+                .unix_timestamp()
+        ));
+    }
+
+    (
+        mate.display_name.clone().unwrap_or(mate.name.clone()),
+        lines.join("\n"),
+        false,
+    )
+}
@@ -1,14 +1,19 @@
 use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
 use mongodb::bson::doc;
 use poise::serenity_prelude::{self as serenity};
 
 use super::{autocomplete::mate as mate_autocomplete, CommandContext};
 use crate::{
-    models::{DBCollective, DBMate, DBMate__new, Signature},
+    models::{DBCollective, DBMate, DBMate__new, Signature, SwitchLog},
     utils::{
-        collectives::{get_or_create_collective, update_switch_logs},
+        avatars::{upload_avatar, upload_banner},
+        collectives::{get_or_create_collective, get_owner_id, update_switch_logs},
+        front::handle_front_change,
+        mates::{get_switched_in_mate, sync_switched_in_mate},
         messages::parse_selector,
-        misc::{envvar, upload_avatar},
+        misc::{envvar, parse_color},
+        time::{parse_birthday, parse_when},
     },
 };
 
@@ -31,13 +36,20 @@ pub async fn create(
     #[description = "a signature to add to any proxied messages (ie `💙- text`)"] signature: Option<
         String,
     >,
+    #[description = "the mate's colour, as a hex code (ie `#ff8800`)"] color: Option<String>,
+    #[description = "the mate's birthday (ie `2001-04-08`, or `04-08` without a year)"]
+    birthday: Option<String>,
+    #[description = "an (optional) banner to show on /info"] banner: Option<serenity::Attachment>,
 ) -> Result<()> {
     let database = &ctx.data().database;
     let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
 
     let old_mate = mates_collection
         .find_one(
-            doc! { "user_id": ctx.author().id.get() as i64, "name": name.clone() },
+            doc! { "user_id": user_id.get() as i64, "name": name.clone() },
             None,
         )
         .await;
@@ -51,13 +63,7 @@ pub async fn create(
         let avatar_url;
 
         if let Some(avatar) = avatar {
-            avatar_url = upload_avatar(
-                &ctx.data().avatar_bucket,
-                ctx.author().id,
-                name.clone(),
-                avatar,
-            )
-            .await?;
+            avatar_url = upload_avatar(ctx.data().avatar_storage.as_ref(), avatar).await?;
         } else {
             avatar_url = envvar("DEFAULT_AVATAR_URL");
         }
@@ -66,9 +72,16 @@ pub async fn create(
         } else {
             None
         };
+        let color = color.as_deref().map(parse_color).transpose()?;
+        let birthday = birthday.as_deref().map(parse_birthday).transpose()?;
+        let banner = if let Some(banner) = banner {
+            Some(upload_banner(ctx.data().avatar_storage.as_ref(), banner).await?)
+        } else {
+            None
+        };
 
         let mate = DBMate__new! {
-            user_id = ctx.author().id.get() as i64,
+            user_id = user_id.get() as i64,
             name = name.clone(),
             is_public = publicity.unwrap_or(true),
             prefix,
@@ -78,6 +91,9 @@ pub async fn create(
             pronouns,
             display_name,
             signature,
+            color,
+            birthday,
+            banner,
             autoproxy = false,
         };
 
@@ -97,82 +113,92 @@ pub async fn switch(
     #[description = "the name of the mate to switch to (removes current switch if not set)"]
     #[autocomplete = "mate_autocomplete"]
     name: Option<String>,
+    #[description = "when the switch happened, if not just now (ie `20m ago` or `2023-04-08 \
+                     14:30`)"]
+    when: Option<String>,
 ) -> Result<()> {
     let database = &ctx.data().database;
     let mates_collection = database.collection::<DBMate>("mates");
     let collectives_collection = database.collection::<DBCollective>("collectives");
 
-    let previous_mate = mates_collection
-        .find_one(
-            doc! {"user_id": ctx.author().id.get() as i64, "autoproxy": true},
-            None,
-        )
-        .await?;
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
 
-    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+    let date = if let Some(when) = &when {
+        parse_when(when, Utc::now(), collective.timezone())?
+    } else {
+        Utc::now()
+    };
+
+    let previous_mate = get_switched_in_mate(&mates_collection, user_id).await?;
 
     if let Some(name) = name {
         let mate = mates_collection
             .find_one(
-                doc! { "user_id": ctx.author().id.get() as i64, "name": name.clone() },
+                doc! { "user_id": user_id.get() as i64, "name": name.clone() },
                 None,
             )
             .await?;
 
         if let Some(mate) = mate {
-            mates_collection
-                .update_one(
-                    doc! { "user_id": mate.user_id, "autoproxy": true },
-                    doc! { "$set": {"autoproxy": false} },
-                    None,
-                )
-                .await?;
-
-            mates_collection
-                .update_one(
-                    doc! { "user_id": mate.user_id, "name": &name },
-                    doc! { "$set": {"autoproxy": true} },
-                    None,
-                )
-                .await?;
-
-            update_switch_logs(
+            let switch_logs = update_switch_logs(
                 &collectives_collection,
                 &collective,
                 Some(mate.id.unwrap()),
-                previous_mate.and_then(|m| Some(m.id.unwrap())),
+                previous_mate.as_ref().and_then(|m| m.id),
+                date,
             )
             .await?;
 
-            ctx.say(format!("Switched to {}!", name)).await?;
+            sync_switched_in_mate(&mates_collection, user_id, &switch_logs).await?;
+
+            if is_latest_switch(&switch_logs, date) {
+                ctx.say(format!("Switched to {}!", name)).await?;
+            } else {
+                ctx.say(format!(
+                    "Logged a switch to {} at <t:{}:f>!",
+                    name,
+                    date.timestamp()
+                ))
+                .await?;
+            }
         } else {
             bail!("You need a mate with that name to switch to them!")
         }
     } else {
-        if previous_mate.is_none() {
+        if previous_mate.is_none() && when.is_none() {
             ctx.say("No mate is currently switched in!").await?;
 
             return Ok(());
         }
 
-        mates_collection
-            .update_one(
-                doc! { "user_id": ctx.author().id.get() as i64, "autoproxy": true },
-                doc! { "$set": {"autoproxy": false} },
-                None,
-            )
-            .await?;
-
-        update_switch_logs(
+        let switch_logs = update_switch_logs(
             &collectives_collection,
             &collective,
             None,
-            previous_mate.and_then(|m| Some(m.id.unwrap())),
+            previous_mate.as_ref().and_then(|m| m.id),
+            date,
         )
         .await?;
 
-        ctx.say("Removed current switch!").await?;
+        sync_switched_in_mate(&mates_collection, user_id, &switch_logs).await?;
+
+        if is_latest_switch(&switch_logs, date) {
+            ctx.say("Removed current switch!").await?;
+        } else {
+            ctx.say(format!("Logged an unswitch at <t:{}:f>!", date.timestamp()))
+                .await?;
+        }
     }
 
+    let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    handle_front_change(ctx.http(), database, &collective, previous_mate, mate).await?;
+
     Ok(())
 }
+
+fn is_latest_switch(switch_logs: &[SwitchLog], date: DateTime<Utc>) -> bool {
+    switch_logs.first().map(|log| log.date) == Some(date)
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::Utc;
 use mongodb::bson::doc;
 use poise::{
@@ -13,8 +13,8 @@ use poise::{
 
 use super::CommandContext;
 use crate::{
-    models::{DBCollective, DBMate, DBMessage, DBUserSettings},
-    utils::misc::envvar,
+    models::{DBCollective, DBGroup, DBMate, DBMateTombstone, DBTrashedMate, DBUserSettings},
+    utils::{backups::create_backup, collectives::get_owner_id, misc::envvar},
 };
 
 /// Get the statistics of the bot
@@ -87,9 +87,21 @@ pub async fn explain(ctx: CommandContext<'_>) -> Result<()> {
     Ok(())
 }
 
-/// Resets your entire collective. THIS DELETES EVERYTHING. THIS CANNOT BE UNDONE. YOU HAVE BEEN WARNED.
+/// Resets your entire collective. THIS DELETES EVERYTHING. (A backup is made first; see /backup list)
 #[poise::command(slash_command, ephemeral)]
 pub async fn reset(ctx: CommandContext<'_>) -> Result<()> {
+    let collectives_collection = ctx
+        .data()
+        .database
+        .collection::<DBCollective>("collectives");
+
+    if get_owner_id(&collectives_collection, ctx.author().id).await? != ctx.author().id {
+        bail!(
+            "Only the account that owns your collective can reset it! Use `/link remove` if you \
+             just want to unlink this account."
+        )
+    }
+
     let reply = CreateReply::default()
         .content(
             "Are you sure you want to do this? Are you ***sure*** you want to ***delete \
@@ -114,7 +126,13 @@ pub async fn reset(ctx: CommandContext<'_>) -> Result<()> {
             let mates_collection = database.collection::<DBMate>("mates");
             let collectives_collection = database.collection::<DBCollective>("collectives");
             let settings_collection = database.collection::<DBUserSettings>("settings");
-            let messages_collection = database.collection::<DBMessage>("messages");
+            let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+            let groups_collection = database.collection::<DBGroup>("groups");
+            let trash_collection = database.collection::<DBTrashedMate>("trash");
+
+            // proxied messages are left alone, since they aren't backed up and are still needed
+            // to delete what's already been sent
+            create_backup(database, ctx.author().id, "before /reset").await?;
 
             mates_collection
                 .delete_many(
@@ -143,7 +161,25 @@ pub async fn reset(ctx: CommandContext<'_>) -> Result<()> {
                 )
                 .await?;
 
-            messages_collection
+            tombstones_collection
+                .delete_many(
+                    doc! {
+                        "user_id": ctx.author().id.get() as i64
+                    },
+                    None,
+                )
+                .await?;
+
+            groups_collection
+                .delete_many(
+                    doc! {
+                        "user_id": ctx.author().id.get() as i64
+                    },
+                    None,
+                )
+                .await?;
+
+            trash_collection
                 .delete_many(
                     doc! {
                         "user_id": ctx.author().id.get() as i64
@@ -157,7 +193,11 @@ pub async fn reset(ctx: CommandContext<'_>) -> Result<()> {
                     &ctx.http(),
                     CreateInteractionResponse::UpdateMessage(
                         CreateInteractionResponseMessage::default()
-                            .content("Your collective has been completely deleted.")
+                            .content(
+                                "Your collective has been completely deleted. Messages you've \
+                                 already proxied are kept, so you can still delete them. (Changed \
+                                 your mind? `/backup restore` can bring everything else back.)",
+                            )
                             .components(vec![]),
                     ),
                 )
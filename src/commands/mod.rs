@@ -1,20 +1,31 @@
 pub mod admin;
 pub mod autocomplete;
+pub mod backup;
+pub mod delegate;
 pub mod delete;
 pub mod edit;
 pub mod export;
+pub mod front;
+pub mod group;
 pub mod import;
 pub mod info;
+pub mod link;
+pub mod list;
 pub mod mate;
 pub mod misc;
+pub mod owner;
+pub mod privacy;
 pub mod reproxy;
 pub mod settings;
 pub mod switch_logs;
+pub mod transfer;
+pub mod trash;
 
 use anyhow::Error;
 use mongodb::{options::FindOneAndUpdateOptions, Database};
 use once_cell::sync::Lazy;
-use s3::Bucket;
+
+use crate::storage::SharedAvatarStorage;
 
 pub static UPSERT_OPTIONS: Lazy<Option<FindOneAndUpdateOptions>> = Lazy::new(|| {
     Some(
@@ -26,6 +37,6 @@ pub static UPSERT_OPTIONS: Lazy<Option<FindOneAndUpdateOptions>> = Lazy::new(||
 
 pub struct Data {
     pub database: Database,
-    pub avatar_bucket: Bucket,
+    pub avatar_storage: SharedAvatarStorage,
 }
 pub type CommandContext<'a> = poise::Context<'a, Data, Error>;
@@ -0,0 +1,52 @@
+use anyhow::Result;
+
+use super::CommandContext;
+use crate::jobs::avatar_gc::{self, GRACE_PERIOD_DAYS};
+
+/// How many deleted images to list before just counting them
+const MAX_LISTED_KEYS: usize = 20;
+
+#[poise::command(slash_command, owners_only, hide_in_help, subcommands("avatar_gc"))]
+pub async fn owner(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Delete stored images that nothing has used for a while
+#[poise::command(slash_command, ephemeral, owners_only)]
+pub async fn avatar_gc(
+    ctx: CommandContext<'_>,
+    #[description = "only report what would be deleted (defaults to true)"] dry_run: Option<bool>,
+) -> Result<()> {
+    let dry_run = dry_run.unwrap_or(true);
+
+    ctx.defer_ephemeral().await?;
+
+    let report = avatar_gc::collect_garbage(
+        &ctx.data().database,
+        ctx.data().avatar_storage.as_ref(),
+        dry_run,
+    )
+    .await?;
+
+    let mut reply = format!(
+        "{}{}.\n(Images are deleted once they've been unused for {} days.)",
+        if dry_run { "Dry run: " } else { "" },
+        report.describe(dry_run),
+        GRACE_PERIOD_DAYS
+    );
+
+    for key in report.deleted.iter().take(MAX_LISTED_KEYS) {
+        reply.push_str(&format!("\n- `{}`", key));
+    }
+
+    if report.deleted.len() > MAX_LISTED_KEYS {
+        reply.push_str(&format!(
+            "\n...and {} more",
+            report.deleted.len() - MAX_LISTED_KEYS
+        ));
+    }
+
+    ctx.say(reply).await?;
+
+    Ok(())
+}
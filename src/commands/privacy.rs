@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use mongodb::bson::{self, doc};
+use poise::ChoiceParameter;
+
+use super::{autocomplete::mate as mate_autocomplete, CommandContext};
+use crate::{
+    models::{CollectivePrivacy, DBCollective, DBMate, MatePrivacy, Privacy},
+    utils::{
+        collectives::{get_or_create_collective, get_owner_id},
+        mates::get_mate,
+    },
+};
+
+#[poise::command(slash_command, subcommands("mate", "collective"))]
+pub async fn privacy(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+#[derive(Clone, Copy, Debug, ChoiceParameter, PartialEq)]
+pub enum PrivacyLevel {
+    #[name = "Public (anyone can see it)"]
+    Public,
+    #[name = "Private (only you can see it)"]
+    Private,
+}
+
+impl From<PrivacyLevel> for Privacy {
+    fn from(level: PrivacyLevel) -> Self {
+        match level {
+            PrivacyLevel::Public => Privacy::Public,
+            PrivacyLevel::Private => Privacy::Private,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ChoiceParameter, PartialEq)]
+pub enum MatePrivacyField {
+    #[name = "Name"]
+    Name,
+    #[name = "Bio"]
+    Description,
+    #[name = "Pronouns"]
+    Pronouns,
+    #[name = "Avatar"]
+    Avatar,
+    #[name = "Birthday"]
+    Birthday,
+    #[name = "Metadata"]
+    Metadata,
+    #[name = "Everything"]
+    All,
+}
+
+#[derive(Clone, Debug, ChoiceParameter, PartialEq)]
+pub enum CollectivePrivacyField {
+    #[name = "Name"]
+    Name,
+    #[name = "Bio"]
+    Description,
+    #[name = "Pronouns"]
+    Pronouns,
+    #[name = "Banner"]
+    Avatar,
+    #[name = "Birthday"]
+    Birthday,
+    #[name = "Metadata"]
+    Metadata,
+    #[name = "Mate list"]
+    MemberList,
+    #[name = "Group list"]
+    GroupList,
+    #[name = "Current fronter"]
+    Front,
+    #[name = "Switch history"]
+    FrontHistory,
+    #[name = "Everything"]
+    All,
+}
+
+/// Change who can see parts of one of your mates
+#[poise::command(slash_command, ephemeral)]
+pub async fn mate(
+    ctx: CommandContext<'_>,
+    #[description = "the mate to change the privacy of"]
+    #[autocomplete = "mate_autocomplete"]
+    mate: String,
+    #[description = "what to change the privacy of (name privacy shows only the display name)"]
+    field: MatePrivacyField,
+    #[description = "who can see it"] level: PrivacyLevel,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let mates_collection = database.collection::<DBMate>("mates");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let mate = get_mate(&mates_collection, user_id, mate)
+        .await
+        .context("Failed to find mate; do they actually exist?")?;
+
+    // fields that were following `is_public` keep doing so until they're set
+    let mut privacy = mate.privacy_settings();
+    let level = Privacy::from(level);
+
+    match field {
+        MatePrivacyField::Name => privacy.name = level,
+        MatePrivacyField::Description => privacy.description = level,
+        MatePrivacyField::Pronouns => privacy.pronouns = level,
+        MatePrivacyField::Avatar => privacy.avatar = level,
+        MatePrivacyField::Birthday => privacy.birthday = level,
+        MatePrivacyField::Metadata => privacy.metadata = level,
+        MatePrivacyField::All => privacy = MatePrivacy::from_single(level),
+    }
+
+    mates_collection
+        .update_one(
+            doc! { "_id": mate.id },
+            doc! { "$set": { "privacy": bson::to_bson(&privacy)? } },
+            None,
+        )
+        .await
+        .context("Failed to update privacy settings; try again later!")?;
+
+    ctx.say(format!(
+        "Made {}'s {} {}!",
+        mate.name,
+        field.name().to_lowercase(),
+        level.as_str()
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Change who can see parts of your collective
+#[poise::command(slash_command, ephemeral)]
+pub async fn collective(
+    ctx: CommandContext<'_>,
+    #[description = "what to change the privacy of"] field: CollectivePrivacyField,
+    #[description = "who can see it"] level: PrivacyLevel,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    let mut privacy = collective.privacy_settings();
+    let level = Privacy::from(level);
+
+    match field {
+        CollectivePrivacyField::Name => privacy.name = level,
+        CollectivePrivacyField::Description => privacy.description = level,
+        CollectivePrivacyField::Avatar => privacy.avatar = level,
+        CollectivePrivacyField::Birthday => privacy.birthday = level,
+        CollectivePrivacyField::Metadata => privacy.metadata = level,
+        CollectivePrivacyField::Pronouns => privacy.pronouns = level,
+        CollectivePrivacyField::MemberList => privacy.member_list = level,
+        CollectivePrivacyField::GroupList => privacy.group_list = level,
+        CollectivePrivacyField::Front => privacy.front = level,
+        CollectivePrivacyField::FrontHistory => privacy.front_history = level,
+        CollectivePrivacyField::All => privacy = CollectivePrivacy::from_single(level),
+    }
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$set": { "privacy": bson::to_bson(&privacy)? } },
+            None,
+        )
+        .await
+        .context("Failed to update privacy settings; try again later!")?;
+
+    ctx.say(format!(
+        "Made your collective's {} {}!",
+        field.name().to_lowercase(),
+        level.as_str()
+    ))
+    .await?;
+
+    Ok(())
+}
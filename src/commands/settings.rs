@@ -1,15 +1,26 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use mongodb::bson::{self, doc};
-use poise::{ChoiceParameter, CreateReply};
+use poise::{
+    serenity_prelude::{self as serenity, EditMember, Permissions, UserId},
+    ChoiceParameter, CreateReply,
+};
 use serde::{Deserialize, Serialize};
 
 use super::CommandContext;
 use crate::{
-    models::{AutoproxySettings, DBUserSettings, Latch},
-    utils::user_settings::{get_or_create_user_settings, update_user_settings},
+    models::{AutoproxySettings, DBCollective, DBMate, DBUserSettings, Latch},
+    utils::{
+        collectives::get_or_create_collective,
+        front::fronter_nickname,
+        mates::get_switched_in_mate,
+        user_settings::{get_or_create_user_settings, update_user_settings},
+    },
 };
 
-#[poise::command(slash_command, subcommands("autoproxy"))]
+#[poise::command(
+    slash_command,
+    subcommands("autoproxy", "switch_feed", "nickname", "birthdays")
+)]
 pub async fn settings(_ctx: CommandContext<'_>) -> Result<()> {
     unreachable!()
 }
@@ -112,3 +123,221 @@ pub async fn autoproxy(
 
     Ok(())
 }
+
+/// Announce your switches in a channel, ie as a shared journal
+#[poise::command(slash_command, ephemeral, guild_only)]
+pub async fn switch_feed(
+    ctx: CommandContext<'_>,
+    #[description = "the channel to announce switches in (resets to none, if unspecified)"]
+    channel: Option<serenity::Channel>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if let Some(channel) = channel {
+        let channel = channel
+            .guild()
+            .context("Your switch feed has to be in a server channel!")?;
+
+        let member = ctx
+            .author_member()
+            .await
+            .context("Failed to check your permissions in that channel!")?;
+
+        let bot_id = ctx.cache().current_user().id;
+        let bot_member = channel
+            .guild_id
+            .member(ctx, bot_id)
+            .await
+            .context("Failed to check my permissions in that channel!")?;
+
+        let (permissions, bot_permissions) = {
+            let guild = ctx
+                .guild()
+                .context("Failed to check your permissions in that channel!")?;
+
+            (
+                guild.user_permissions_in(&channel, &member),
+                guild.user_permissions_in(&channel, &bot_member),
+            )
+        };
+
+        if !permissions.contains(Permissions::SEND_MESSAGES) {
+            bail!("You can't send messages in that channel!");
+        }
+
+        // the feed's sent through a webhook, same as proxied messages
+        if !bot_permissions.contains(Permissions::MANAGE_WEBHOOKS) {
+            bail!("I need the Manage Webhooks permission in that channel to post your switches!");
+        }
+
+        collectives_collection
+            .update_one(
+                doc! { "user_id": collective.user_id },
+                doc! { "$set": { "switch_feed_channel_id": channel.id.get() as i64 } },
+                None,
+            )
+            .await?;
+
+        ctx.say(format!("Set your switch feed channel to <#{}>", channel.id))
+            .await?;
+    } else {
+        collectives_collection
+            .update_one(
+                doc! { "user_id": collective.user_id },
+                doc! { "$unset": { "switch_feed_channel_id": "" } },
+                None,
+            )
+            .await?;
+
+        ctx.say("Disabled your switch feed!").await?;
+    }
+
+    Ok(())
+}
+
+/// Change your nickname in this server to whoever's switched in
+#[poise::command(slash_command, ephemeral, guild_only)]
+pub async fn nickname(
+    ctx: CommandContext<'_>,
+    #[description = "whether to change your nickname here when you switch"] enabled: bool,
+) -> Result<()> {
+    let database = &ctx.data().database;
+
+    let settings_collection = database.collection::<DBUserSettings>("settings");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    let guild_id = ctx
+        .guild_id()
+        .context("You cannot set nickname settings in DMs.")?;
+
+    let settings = get_or_create_user_settings(
+        &settings_collection,
+        ctx.author().id,
+        Some(guild_id.get() as i64),
+    )
+    .await?;
+
+    let member = ctx
+        .author_member()
+        .await
+        .context("Failed to get your server profile!")?;
+
+    let already_enabled = settings.fronter_nickname == Some(true);
+
+    if enabled {
+        // don't overwrite the nickname from before we started changing it
+        let original_nickname = if already_enabled {
+            settings.original_nickname.clone()
+        } else {
+            member.nick.clone()
+        };
+
+        settings_collection
+            .update_one(
+                doc! {
+                    "user_id": settings.user_id as i64,
+                    "guild_id": settings.guild_id
+                },
+                doc! { "$set": { "fronter_nickname": true, "original_nickname": original_nickname } },
+                None,
+            )
+            .await?;
+
+        let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+        if let Some(mate) =
+            get_switched_in_mate(&mates_collection, UserId::new(collective.user_id as u64)).await?
+        {
+            guild_id
+                .edit_member(
+                    ctx.http(),
+                    ctx.author().id,
+                    EditMember::new().nickname(fronter_nickname(&mate, &collective)),
+                )
+                .await
+                .context("Failed to change your nickname; does Multiplex have permission to?")?;
+        }
+
+        ctx.say("Your nickname here will now change to whoever's switched in!")
+            .await?;
+    } else {
+        settings_collection
+            .update_one(
+                doc! {
+                    "user_id": settings.user_id as i64,
+                    "guild_id": settings.guild_id
+                },
+                doc! { "$set": { "fronter_nickname": false, "original_nickname": None::<String> } },
+                None,
+            )
+            .await?;
+
+        if already_enabled {
+            guild_id
+                .edit_member(
+                    ctx.http(),
+                    ctx.author().id,
+                    EditMember::new()
+                        .nickname(settings.original_nickname.clone().unwrap_or_default()),
+                )
+                .await
+                .context("Failed to change your nickname; does Multiplex have permission to?")?;
+        }
+
+        ctx.say("Your nickname here will no longer change when you switch!")
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Announce your collective's birthdays in this server's birthday channel
+#[poise::command(slash_command, ephemeral, guild_only)]
+pub async fn birthdays(
+    ctx: CommandContext<'_>,
+    #[description = "whether to announce your birthdays here"] enabled: bool,
+) -> Result<()> {
+    let database = &ctx.data().database;
+
+    let settings_collection = database.collection::<DBUserSettings>("settings");
+
+    let guild_id = ctx
+        .guild_id()
+        .context("You cannot set birthday settings in DMs.")?;
+
+    let settings = get_or_create_user_settings(
+        &settings_collection,
+        ctx.author().id,
+        Some(guild_id.get() as i64),
+    )
+    .await?;
+
+    settings_collection
+        .update_one(
+            doc! {
+                "user_id": settings.user_id as i64,
+                "guild_id": settings.guild_id
+            },
+            doc! { "$set": { "birthday_announcements": enabled } },
+            None,
+        )
+        .await?;
+
+    if enabled {
+        ctx.say(
+            "Your public mates' birthdays will now be announced here (if the server has a \
+             birthday channel)!",
+        )
+        .await?;
+    } else {
+        ctx.say("Your birthdays will no longer be announced here!")
+            .await?;
+    }
+
+    Ok(())
+}
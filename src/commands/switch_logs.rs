@@ -1,77 +1,254 @@
-use anyhow::Result;
-use mongodb::bson::doc;
-use poise::{serenity_prelude::CreateEmbed, CreateReply};
+use std::collections::HashMap;
 
-use super::CommandContext;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use mongodb::bson::{doc, oid::ObjectId};
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed, UserId},
+    CreateReply,
+};
+
+use super::{autocomplete::mate as mate_autocomplete, CommandContext};
 use crate::{
-    models::{DBCollective, DBMate},
-    utils::collectives::get_or_create_collective,
+    models::{DBCollective, DBMate, DBMateTombstone, SwitchLog},
+    utils::{
+        collectives::{
+            get_or_create_collective, get_owner_id, normalize_switch_logs, save_switch_logs,
+        },
+        front::handle_front_change,
+        mates::{
+            get_all_mates, get_mate, get_mate_names, get_switched_in_mate, sync_switched_in_mate,
+        },
+        time::{format_in_timezone, parse_when},
+    },
 };
 
-/// Shows the last 5 switches for your collective
+const SWITCHES_PER_PAGE: usize = 10;
+
+#[poise::command(slash_command, subcommands("list", "edit", "delete"))]
+pub async fn switch_logs(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Shows the most recent switches for a collective
 #[poise::command(slash_command, ephemeral)]
-pub async fn switch_logs(ctx: CommandContext<'_>) -> Result<()> {
+pub async fn list(
+    ctx: CommandContext<'_>,
+    #[description = "the page of switches to show (defaults to the most recent)"]
+    #[min = 1]
+    page: Option<u32>,
+    #[description = "the user whose switches you want to see (defaults to you if unspecified)"]
+    user: Option<serenity::User>,
+) -> Result<()> {
     let database = &ctx.data().database;
     let collectives_collection = database.collection::<DBCollective>("collectives");
     let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
 
-    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+    let user_id = get_owner_id(
+        &collectives_collection,
+        user.map(|user| user.id).unwrap_or(ctx.author().id),
+    )
+    .await?;
+    let is_own = user_id == get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let collective = get_or_create_collective(&collectives_collection, user_id).await?;
+
+    if !collective.is_public && !is_own {
+        bail!("That user's collective is private!");
+    }
 
-    if let Some(switch_logs) = collective.switch_logs {
-        let mut fields = vec![];
-
-        for log in switch_logs {
-            if log.unswitch {
-                let previous_mate = mates_collection
-                    .find_one(doc! { "_id": log.previous_mate_id.unwrap() /* on an unswitch the previous mate will always exist */ }, None)
-                    .await?
-                    .unwrap(); // there's no way for the mate to not exist
-
-                fields.push((
-                    log.date.to_string(),
-                    format!(
-                        "Unswitched from {}",
-                        previous_mate.display_name.unwrap_or(previous_mate.name)
-                    ),
-                    false,
-                ));
-            } else {
-                let mate = mates_collection
-                    .find_one(doc! { "_id": log.mate_id.unwrap() /* no way for `mate_id` to not exist in this case */ }, None)
-                    .await?
-                    .unwrap(); // there's no way for the mate to not exist
-
-                if let Some(previous_mate_id) = log.previous_mate_id {
-                    let previous_mate = mates_collection
-                        .find_one(doc! { "_id": previous_mate_id }, None)
-                        .await?
-                        .unwrap(); // there's no way for the mate to not exist
-
-                    fields.push((
-                        log.date.to_string(),
-                        format!(
-                            "Switched from {} to {}",
-                            previous_mate.display_name.unwrap_or(previous_mate.name),
-                            mate.display_name.unwrap_or(mate.name)
-                        ),
-                        false,
-                    ));
-                } else {
-                    fields.push((
-                        log.date.to_string(),
-                        format!("Switched to {}", mate.display_name.unwrap_or(mate.name)),
-                        false,
-                    ));
-                }
+    if !collective.privacy_settings().front_history.allows(is_own) {
+        bail!("That collective's switch history is private!");
+    }
+
+    let timezone = collective.timezone();
+    let switch_logs = collective.switch_logs.unwrap_or_default();
+
+    if switch_logs.is_empty() {
+        ctx.say(if is_own {
+            "You have never switched!"
+        } else {
+            "That collective has never switched!"
+        })
+        .await?;
+
+        return Ok(());
+    }
+
+    let mut mate_names = get_mate_names(&mates_collection, &tombstones_collection, user_id).await?;
+
+    if !is_own {
+        for mate in get_all_mates(&mates_collection, user_id).await? {
+            if let Some(id) = mate.id.filter(|_| !mate.is_public) {
+                mate_names.insert(id, "a private mate".to_string());
             }
         }
+    }
+
+    let start = (page.unwrap_or(1).max(1) as usize - 1) * SWITCHES_PER_PAGE;
+
+    if start >= switch_logs.len() {
+        bail!("There aren't that many switches!")
+    }
+
+    let fields = switch_logs
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(SWITCHES_PER_PAGE)
+        .map(|(i, log)| {
+            (
+                // timestamps don't render in field names, so this is in the collective's timezone
+                format!("#{} - {}", i + 1, format_in_timezone(log.date, timezone)),
+                format!(
+                    "{} (<t:{}:R>)",
+                    describe_switch(log, &mate_names),
+                    log.date.timestamp()
+                ),
+                false,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "Switches {}-{} of {}",
+            start + 1,
+            start + fields.len(),
+            switch_logs.len()
+        ))
+        .fields(fields);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Fix a logged switch (its number is shown in `/switch_logs list`)
+#[poise::command(slash_command, ephemeral)]
+pub async fn edit(
+    ctx: CommandContext<'_>,
+    #[description = "the number of the switch to edit"]
+    #[min = 1]
+    entry: u32,
+    #[description = "when the switch actually happened (ie `20m ago` or `2023-04-08 14:30`)"]
+    when: Option<String>,
+    #[description = "the mate that actually switched in"]
+    #[autocomplete = "mate_autocomplete"]
+    mate: Option<String>,
+    #[description = "if true, turns the switch into an unswitch"] unswitch: Option<bool>,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+
+    if mate.is_some() && unswitch == Some(true) {
+        bail!("A switch can't be to a mate and an unswitch at the same time; pick one!");
+    }
+
+    if mate.is_none() && unswitch == Some(false) {
+        bail!("A switch has to be to someone; pick the mate that actually switched in!");
+    }
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+    let user_id = UserId::new(collective.user_id as u64);
+
+    let mut switch_logs = collective.switch_logs.clone().unwrap_or_default();
+
+    let log = (entry as usize)
+        .checked_sub(1)
+        .and_then(|i| switch_logs.get_mut(i))
+        .context("That switch doesn't exist!")?;
+
+    if let Some(when) = when {
+        log.date = parse_when(&when, Utc::now(), collective.timezone())?;
+    }
+
+    if let Some(mate) = mate {
+        let mate = get_mate(&mates_collection, user_id, mate)
+            .await
+            .context("Failed to find mate; do they actually exist?")?;
+
+        log.mate_id = mate.id;
+    } else if unswitch == Some(true) {
+        log.mate_id = None;
+    }
+
+    normalize_switch_logs(&mut switch_logs);
+
+    let previous_mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    save_switch_logs(&collectives_collection, collective.user_id, &switch_logs).await?;
+    sync_switched_in_mate(&mates_collection, user_id, &switch_logs).await?;
+
+    let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    handle_front_change(ctx.http(), database, &collective, previous_mate, mate).await?;
+
+    ctx.say("Successfully edited switch!").await?;
+
+    Ok(())
+}
+
+/// Delete a logged switch (its number is shown in `/switch_logs list`)
+#[poise::command(slash_command, ephemeral)]
+pub async fn delete(
+    ctx: CommandContext<'_>,
+    #[description = "the number of the switch to delete"]
+    #[min = 1]
+    entry: u32,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
 
-        let embed = CreateEmbed::new().title("Last 5 switches").fields(fields);
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+    let user_id = UserId::new(collective.user_id as u64);
 
-        ctx.send(CreateReply::default().embed(embed)).await?;
-    } else {
-        ctx.say("You have never switched!").await?;
+    let mut switch_logs = collective.switch_logs.clone().unwrap_or_default();
+
+    if entry == 0 || entry as usize > switch_logs.len() {
+        bail!("That switch doesn't exist!")
     }
 
+    switch_logs.remove(entry as usize - 1);
+
+    normalize_switch_logs(&mut switch_logs);
+
+    let previous_mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    save_switch_logs(&collectives_collection, collective.user_id, &switch_logs).await?;
+    sync_switched_in_mate(&mates_collection, user_id, &switch_logs).await?;
+
+    let mate = get_switched_in_mate(&mates_collection, user_id).await?;
+
+    handle_front_change(ctx.http(), database, &collective, previous_mate, mate).await?;
+
+    ctx.say("Successfully deleted switch! o7").await?;
+
     Ok(())
 }
+
+fn describe_switch(log: &SwitchLog, mate_names: &HashMap<ObjectId, String>) -> String {
+    let name = |mate_id: ObjectId| {
+        mate_names
+            .get(&mate_id)
+            .cloned()
+            .unwrap_or("an unknown mate".to_string())
+    };
+
+    match (log.unswitch, log.mate_id, log.previous_mate_id) {
+        (true, _, Some(previous_mate_id)) => {
+            format!("Unswitched from {}", name(previous_mate_id))
+        }
+        (true, _, None) => "Unswitched".to_string(),
+        (false, Some(mate_id), Some(previous_mate_id)) => format!(
+            "Switched from {} to {}",
+            name(previous_mate_id),
+            name(mate_id)
+        ),
+        (false, Some(mate_id), None) => format!("Switched to {}", name(mate_id)),
+        (false, None, _) => "Switched to an unknown mate".to_string(),
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{bail, Context, Result};
+use mongodb::bson::doc;
+use poise::serenity_prelude::{self as serenity};
+
+use super::CommandContext;
+use crate::{
+    models::{
+        DBBackup, DBCollective, DBGroup, DBMate, DBMateTombstone, DBMessage, DBTrashedMate,
+        DBUserSettings,
+    },
+    utils::{
+        backups::create_backup,
+        collectives::{get_or_create_collective, get_owner_id},
+    },
+};
+
+#[poise::command(slash_command, subcommands("start", "accept", "cancel"))]
+pub async fn transfer(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// Start moving your whole collective to another account (it has to `/transfer accept`)
+#[poise::command(slash_command, ephemeral)]
+pub async fn start(
+    ctx: CommandContext<'_>,
+    #[description = "the account to move your collective to"] user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    if user.bot {
+        bail!("You can't transfer your collective to a bot!");
+    }
+
+    if user.id == ctx.author().id {
+        bail!("You already own your collective!");
+    }
+
+    if get_owner_id(&collectives_collection, ctx.author().id).await? != ctx.author().id {
+        bail!("Only the account that owns your collective can transfer it!");
+    }
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$set": { "pending_transfer": user.id.get() as i64 } },
+            None,
+        )
+        .await?;
+
+    ctx.say(format!(
+        "Started transferring your collective to <@{}>! Run `/transfer accept` on that account to \
+         finish, or `/transfer cancel` to stop.",
+        user.id
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Take ownership of a collective that's being transferred to this account
+#[poise::command(slash_command, ephemeral)]
+pub async fn accept(
+    ctx: CommandContext<'_>,
+    #[description = "the account the collective is being transferred from"] user: serenity::User,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+    let settings_collection = database.collection::<DBUserSettings>("settings");
+    let messages_collection = database.collection::<DBMessage>("messages");
+    let groups_collection = database.collection::<DBGroup>("groups");
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+    let backups_collection = database.collection::<DBBackup>("backups");
+
+    let old_id = user.id.get() as i64;
+    let new_id = ctx.author().id.get() as i64;
+
+    collectives_collection
+        .find_one(doc! { "user_id": old_id, "pending_transfer": new_id }, None)
+        .await?
+        .context("That account isn't transferring its collective to this one!")?;
+
+    let owner_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    if owner_id != ctx.author().id && owner_id != user.id {
+        bail!("This account is linked to another collective! Use `/link remove` first.");
+    }
+
+    if mates_collection
+        .find_one(doc! { "user_id": new_id }, None)
+        .await?
+        .is_some()
+    {
+        bail!(
+            "This account already has mates of its own! Export and `/reset` it first, then accept \
+             the transfer."
+        );
+    }
+
+    // its collective is about to be replaced, which would leave any linked accounts pointing at
+    // nothing
+    if collectives_collection
+        .find_one(doc! { "user_id": new_id }, None)
+        .await?
+        .is_some_and(|collective| {
+            collective
+                .linked_accounts
+                .is_some_and(|accounts| !accounts.is_empty())
+        })
+    {
+        bail!(
+            "This account has other accounts linked to it! Unlink them with `/link remove` first, \
+             then accept the transfer."
+        );
+    }
+
+    // so whatever this account had set up can be brought back with `/backup restore`
+    create_backup(database, ctx.author().id, "before accepting a transfer").await?;
+
+    // everything moves at once so a failure halfway can't split a collective across accounts
+    let mut session = collectives_collection
+        .client()
+        .start_session(None)
+        .await
+        .context("Failed to start transfer; try again later!")?;
+
+    session.start_transaction(None).await?;
+
+    // this account's own (empty) collective and settings are replaced by the transferred ones
+    collectives_collection
+        .delete_one_with_session(doc! { "user_id": new_id }, None, &mut session)
+        .await?;
+    settings_collection
+        .delete_many_with_session(doc! { "user_id": new_id }, None, &mut session)
+        .await?;
+
+    collectives_collection
+        .update_one_with_session(
+            doc! { "user_id": old_id },
+            doc! {
+                "$set": { "user_id": new_id },
+                "$unset": { "pending_transfer": "" },
+                "$pull": { "linked_accounts": new_id, "pending_links": new_id }
+            },
+            None,
+            &mut session,
+        )
+        .await?;
+
+    // keep the old account's place as a delegate in other collectives
+    collectives_collection
+        .update_many_with_session(
+            doc! { "delegates.user_id": old_id },
+            doc! { "$set": { "delegates.$.user_id": new_id } },
+            None,
+            &mut session,
+        )
+        .await?;
+
+    let update = doc! { "$set": { "user_id": new_id } };
+
+    mates_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    tombstones_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    groups_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    trash_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    settings_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    messages_collection
+        .update_many_with_session(
+            doc! { "user_id": old_id },
+            update.clone(),
+            None,
+            &mut session,
+        )
+        .await?;
+    // so the new account can still manage the messages the old one sent itself
+    messages_collection
+        .update_many_with_session(
+            doc! { "sender_id": old_id },
+            doc! { "$set": { "sender_id": new_id } },
+            None,
+            &mut session,
+        )
+        .await?;
+    // otherwise the old account could restore the whole collective under its own id
+    backups_collection
+        .update_many_with_session(doc! { "user_id": old_id }, update, None, &mut session)
+        .await?;
+
+    session
+        .commit_transaction()
+        .await
+        .context("Failed to transfer collective; nothing was changed, so try again later!")?;
+
+    ctx.say(format!(
+        "Transferred <@{}>'s collective to this account! Your mates, groups, switches, settings, \
+         messages and backups are all here now.",
+        user.id
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Stop a transfer you've started
+#[poise::command(slash_command, ephemeral)]
+pub async fn cancel(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    let collective = get_or_create_collective(&collectives_collection, ctx.author().id).await?;
+
+    if collective.user_id != ctx.author().id.get() as i64 {
+        bail!("Only the account that owns your collective can cancel a transfer!");
+    }
+
+    if collective.pending_transfer.is_none() {
+        bail!("You aren't transferring your collective!");
+    }
+
+    collectives_collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$unset": { "pending_transfer": "" } },
+            None,
+        )
+        .await?;
+
+    ctx.say("Cancelled the transfer!").await?;
+
+    Ok(())
+}
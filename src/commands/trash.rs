@@ -0,0 +1,94 @@
+use anyhow::{bail, Context, Result};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+use super::{autocomplete::trashed_mate as trashed_mate_autocomplete, CommandContext};
+use crate::{
+    models::{DBCollective, DBMate, DBMateTombstone, DBTrashedMate},
+    utils::{
+        collectives::get_owner_id,
+        trash::{get_trashed_mates, purge_date, restore_mate, retention_days},
+    },
+};
+
+#[poise::command(slash_command, subcommands("list", "restore"))]
+pub async fn trash(_ctx: CommandContext<'_>) -> Result<()> {
+    unreachable!()
+}
+
+/// List deleted mates that can still be brought back
+#[poise::command(slash_command, ephemeral)]
+pub async fn list(ctx: CommandContext<'_>) -> Result<()> {
+    let database = &ctx.data().database;
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let trashed = get_trashed_mates(&trash_collection, user_id).await?;
+
+    if trashed.is_empty() {
+        bail!("The trash is empty!");
+    }
+
+    let lines = trashed
+        .iter()
+        .map(|trashed| {
+            format!(
+                "**{}**: deleted <t:{}:R>, gone for good <t:{}:R>",
+                trashed.name,
+                trashed.deleted_at.timestamp(),
+                purge_date(trashed).timestamp()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let embed = CreateEmbed::new()
+        .title("Trash")
+        .description(lines.join("\n"))
+        .footer(CreateEmbedFooter::new(format!(
+            "Deleted mates are kept for {} days; bring them back with /trash restore",
+            retention_days()
+        )));
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Bring back a deleted mate
+#[poise::command(slash_command, ephemeral)]
+pub async fn restore(
+    ctx: CommandContext<'_>,
+    #[description = "name of the deleted mate"]
+    #[autocomplete = "trashed_mate_autocomplete"]
+    name: String,
+) -> Result<()> {
+    let database = &ctx.data().database;
+    let mates_collection = database.collection::<DBMate>("mates");
+    let tombstones_collection = database.collection::<DBMateTombstone>("tombstones");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+    let trash_collection = database.collection::<DBTrashedMate>("trash");
+
+    let user_id = get_owner_id(&collectives_collection, ctx.author().id).await?;
+
+    let trashed = get_trashed_mates(&trash_collection, user_id)
+        .await?
+        .into_iter()
+        .find(|trashed| trashed.name == name)
+        .context("Couldn't find a deleted mate with that name; check `/trash list`!")?;
+
+    restore_mate(
+        &mates_collection,
+        &tombstones_collection,
+        &trash_collection,
+        trashed,
+    )
+    .await?;
+
+    ctx.say(format!("Brought {} back!", name)).await?;
+
+    Ok(())
+}
@@ -1,12 +1,12 @@
 use anyhow::Result;
-use poise::serenity_prelude::{CacheHttp, Context as SerenityContext, MessageUpdateEvent, Message};
+use poise::serenity_prelude::{CacheHttp, Context as SerenityContext, MessageUpdateEvent, Message, UserId};
 
 use crate::{
     commands::Data,
     models::{DBCollective, DBMate, DBUserSettings},
     utils::{
-        collectives::get_or_create_collective,
-        mates::{get_all_mates, get_autoproxied_mate, get_matching_mate},
+        collectives::{get_or_create_collective_without_switch_logs, get_owner_id},
+        mates::{get_all_mates, get_autoproxied_mate, get_delegated_mates, get_matching_mate},
         messages::send_proxied_message,
     },
 };
@@ -25,15 +25,38 @@ pub async fn run(ctx: &SerenityContext, data: &Data, event_message: &MessageUpda
     let mut message = Message::default();
     event_message.apply_to_message(&mut message);
     
-    let mates = get_all_mates(&mates_collection, message.author.id).await?;
+    // linked accounts proxy as the collective they're linked to, but keep their own settings
+    let user_id = get_owner_id(&collectives_collection, message.author.id).await?;
 
-    if mates.len() == 0 {
+    let mates = get_all_mates(&mates_collection, user_id).await?;
+    let delegated_mates =
+        get_delegated_mates(&mates_collection, &collectives_collection, message.author.id).await?;
+
+    if mates.is_empty() && delegated_mates.is_empty() {
         return Ok(());
     }
 
     let mut mate = get_matching_mate(&mates, &message.content);
 
     if mate.is_none() {
+        // mates someone's a delegate for only proxy with their selectors, never with autoproxy
+        if let Some(delegated_mate) = get_matching_mate(&delegated_mates, &message.content) {
+            let collective = get_or_create_collective_without_switch_logs(
+                &collectives_collection,
+                UserId::new(delegated_mate.user_id as u64),
+            )
+            .await?;
+
+            return send_proxied_message(
+                ctx.http(),
+                &message,
+                delegated_mate.clone(),
+                collective,
+                database,
+            )
+            .await;
+        }
+
         mate = get_autoproxied_mate(
             &settings_collection,
             &mates,
@@ -45,7 +68,7 @@ pub async fn run(ctx: &SerenityContext, data: &Data, event_message: &MessageUpda
 
     if let Some(mate) = mate {
         let collective =
-            get_or_create_collective(&collectives_collection, message.author.id).await?;
+            get_or_create_collective_without_switch_logs(&collectives_collection, user_id).await?;
 
         return send_proxied_message(ctx.http(), &message, mate.clone(), collective, database)
             .await;
@@ -1,12 +1,14 @@
 use anyhow::{Context, Result};
-use poise::serenity_prelude::{CacheHttp, Context as SerenityContext, Message, MessageFlags};
+use poise::serenity_prelude::{
+    CacheHttp, Context as SerenityContext, Message, MessageFlags, UserId,
+};
 
 use crate::{
     commands::Data,
     models::{DBCollective, DBMate, DBUserSettings},
     utils::{
-        collectives::get_or_create_collective,
-        mates::{get_all_mates, get_autoproxied_mate, get_matching_mate},
+        collectives::{get_or_create_collective_without_switch_logs, get_owner_id},
+        mates::{get_all_mates, get_autoproxied_mate, get_delegated_mates, get_matching_mate},
         messages::send_proxied_message,
         user_settings::update_latch,
     },
@@ -32,9 +34,18 @@ pub async fn run(ctx: &SerenityContext, data: &Data, message: &Message) -> Resul
     let collectives_collection = database.collection::<DBCollective>("collectives");
     let settings_collection = database.collection::<DBUserSettings>("settings");
 
-    let mates = get_all_mates(&mates_collection, message.author.id).await?;
+    // linked accounts proxy as the collective they're linked to, but keep their own settings
+    let user_id = get_owner_id(&collectives_collection, message.author.id).await?;
+
+    let mates = get_all_mates(&mates_collection, user_id).await?;
+    let delegated_mates = get_delegated_mates(
+        &mates_collection,
+        &collectives_collection,
+        message.author.id,
+    )
+    .await?;
 
-    if mates.len() == 0 {
+    if mates.is_empty() && delegated_mates.is_empty() {
         return Ok(());
     }
 
@@ -50,6 +61,24 @@ pub async fn run(ctx: &SerenityContext, data: &Data, message: &Message) -> Resul
     }
 
     if mate.is_none() {
+        // mates someone's a delegate for only proxy with their selectors, never with autoproxy
+        if let Some(delegated_mate) = get_matching_mate(&delegated_mates, &message.content) {
+            let collective = get_or_create_collective_without_switch_logs(
+                &collectives_collection,
+                UserId::new(delegated_mate.user_id as u64),
+            )
+            .await?;
+
+            return send_proxied_message(
+                ctx.http(),
+                message,
+                delegated_mate.clone(),
+                collective,
+                database,
+            )
+            .await;
+        }
+
         mate = get_autoproxied_mate(
             &settings_collection,
             &mates,
@@ -68,7 +97,7 @@ pub async fn run(ctx: &SerenityContext, data: &Data, message: &Message) -> Resul
 
     if let Some(mate) = mate {
         let collective =
-            get_or_create_collective(&collectives_collection, message.author.id).await?;
+            get_or_create_collective_without_switch_logs(&collectives_collection, user_id).await?;
 
         return send_proxied_message(ctx.http(), message, mate.clone(), collective, database).await;
     }
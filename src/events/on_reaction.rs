@@ -1,15 +1,18 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use mongodb::bson::doc;
 use poise::serenity_prelude::{
     CacheHttp, Context as SerenityContext, CreateEmbed, CreateMessage, Reaction, UserId,
 };
 
 use crate::{
     commands::Data,
-    models::{DBChannel, DBMate, DBMessage},
+    models::{DBChannel, DBCollective, DBMate, DBMateTombstone, DBMessage, DelegatePermission},
     utils::{
         channels::get_webhook_or_create,
-        mates::get_mate,
-        messages::{clamp_message_length, delete_dbmessage, get_message},
+        collectives::get_owner_id,
+        mates::{get_mate, get_tombstone},
+        messages::{can_manage_message, clamp_message_length, delete_dbmessage, get_message},
+        time::format_in_timezone,
     },
 };
 
@@ -17,14 +20,29 @@ pub async fn run(ctx: &SerenityContext, data: &Data, reaction: &Reaction) -> Res
     let database = &data.database;
     let messages_collection = database.collection::<DBMessage>("messages");
     let channels_collection = database.collection::<DBChannel>("channels");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
 
-    let original_message = get_message(&messages_collection, None, reaction.message_id).await?;
+    let original_message = get_message(
+        &messages_collection,
+        &collectives_collection,
+        None,
+        DelegatePermission::DeleteMessages,
+        reaction.message_id,
+    )
+    .await?;
 
     let (webhook, thread_id) =
         get_webhook_or_create(ctx.http(), &channels_collection, reaction.channel_id).await?;
 
     if reaction.emoji.unicode_eq("❌") {
-        if original_message.user_id == reaction.user_id.unwrap().get() {
+        if can_manage_message(
+            &collectives_collection,
+            &original_message,
+            reaction.user_id.unwrap(),
+            DelegatePermission::DeleteMessages,
+        )
+        .await?
+        {
             webhook
                 .delete_message(ctx.http(), thread_id, reaction.message_id)
                 .await?;
@@ -37,6 +55,24 @@ pub async fn run(ctx: &SerenityContext, data: &Data, reaction: &Reaction) -> Res
             .get_message(reaction.channel_id, reaction.message_id)
             .await?;
 
+        // the collective's owner, for messages from before the real sender was kept
+        let sender_id = original_message
+            .sender_id
+            .unwrap_or(original_message.user_id);
+
+        let sender_owner_id = get_owner_id(&collectives_collection, UserId::new(sender_id)).await?;
+        let is_own = get_owner_id(&collectives_collection, reaction.user_id.unwrap())
+            .await?
+            .get()
+            == original_message.user_id;
+
+        // only worth showing the sender's local time if they've actually set a timezone
+        let sender_timezone = collectives_collection
+            .find_one(doc! { "user_id": sender_owner_id.get() as i64 }, None)
+            .await?
+            .filter(|collective| collective.timezone.is_some())
+            .map(|collective| collective.timezone());
+
         reaction
             .user(ctx.http())
             .await?
@@ -46,24 +82,38 @@ pub async fn run(ctx: &SerenityContext, data: &Data, reaction: &Reaction) -> Res
                     //.content(format!("Message sent by <@{}>", original_message.user_id)),
                     .embeds(vec![CreateEmbed::new()
                         .title("Message Info")
-                        .field("User", format!("<@{}>", original_message.user_id), false)
+                        .field("User", format!("<@{}>", sender_id), false)
                         .field(
                             "Mate",
                             if let Some(mate_name) = original_message.mate_name {
                                 let mates_collection = database.collection::<DBMate>("mates");
+                                let tombstones_collection =
+                                    database.collection::<DBMateTombstone>("tombstones");
+
+                                let user_id = UserId::new(original_message.user_id);
 
-                                let mate = get_mate(
-                                    &mates_collection,
-                                    UserId::new(original_message.user_id),
-                                    mate_name,
+                                if let Some(mate) =
+                                    get_mate(&mates_collection, user_id, mate_name.clone()).await
+                                {
+                                    let name_allowed = mate.privacy_settings().name.allows(is_own);
+
+                                    match mate.display_name {
+                                        Some(display_name) if name_allowed => {
+                                            format!("{} ({})", display_name, mate.name)
+                                        }
+                                        Some(display_name) => display_name,
+                                        None => mate.name.clone(),
+                                    }
+                                } else if let Some(tombstone) = get_tombstone(
+                                    &tombstones_collection,
+                                    user_id,
+                                    mate_name.clone(),
                                 )
                                 .await
-                                .context("Failed to get mate!")?;
-
-                                if let Some(display_name) = mate.display_name {
-                                    format!("{} ({})", display_name, mate.name)
+                                {
+                                    tombstone.label()
                                 } else {
-                                    format!("{}", mate.name)
+                                    format!("{} (deleted)", mate_name)
                                 }
                             } else {
                                 "Unknown".to_string()
@@ -81,7 +131,15 @@ pub async fn run(ctx: &SerenityContext, data: &Data, reaction: &Reaction) -> Res
                         )
                         .field(
                             "Timestamp",
-                            format!("<t:{}>", webhook_message.timestamp.timestamp()),
+                            if let Some(timezone) = sender_timezone {
+                                format!(
+                                    "<t:{}>\n{} for the sender",
+                                    webhook_message.timestamp.timestamp(),
+                                    format_in_timezone(*webhook_message.timestamp, timezone)
+                                )
+                            } else {
+                                format!("<t:{}>", webhook_message.timestamp.timestamp())
+                            },
                             false,
                         )]),
             )
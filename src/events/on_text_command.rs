@@ -7,14 +7,15 @@ use poise::serenity_prelude::{
 
 use crate::{
     commands::Data,
-    models::{DBChannel, DBMessage},
-    utils::misc::envvar,
+    models::{DBChannel, DBCollective, DBMessage, DelegatePermission},
+    utils::{collectives::get_owner_id, messages::get_message, misc::envvar},
 };
 
 pub async fn run(ctx: &SerenityContext, data: &Data, message: &Message) -> Result<()> {
     let database = &data.database;
     let messages_collection = database.collection::<DBMessage>("messages");
     let channels_collection = database.collection::<DBChannel>("channels");
+    let collectives_collection = database.collection::<DBCollective>("collectives");
 
     match message
         .content
@@ -27,20 +28,24 @@ pub async fn run(ctx: &SerenityContext, data: &Data, message: &Message) -> Resul
         "edit" | "e" => {
             let message_id;
             if let Some(message_ref) = message.referenced_message.clone() {
-                let message = messages_collection
-                    .find_one(
-                        doc! { "user_id": message.author.id.get() as i64, "message_id": message_ref.id.get() as i64 },
-                        Some(FindOneOptions::builder().sort(doc! {"_id": -1}).build()),
-                    ).await;
-                if let Ok(Some(_)) = message {
-                    message_id = message_ref.id
-                } else {
-                    return Err(anyhow::anyhow!("You don't own that message"));
-                }
+                // this also lets delegates with edit permissions through
+                get_message(
+                    &messages_collection,
+                    &collectives_collection,
+                    Some(message.author.id),
+                    DelegatePermission::EditMessages,
+                    message_ref.id,
+                )
+                .await
+                .map_err(|_| anyhow::anyhow!("You don't own that message"))?;
+
+                message_id = message_ref.id
             } else {
+                let user_id = get_owner_id(&collectives_collection, message.author.id).await?;
+
                 let message = messages_collection
                     .find_one(
-                        doc! { "user_id": message.author.id.get() as i64 },
+                        doc! { "user_id": user_id.get() as i64 },
                         Some(FindOneOptions::builder().sort(doc! {"_id": -1}).build()),
                     )
                     .await?
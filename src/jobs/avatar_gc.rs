@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, Document},
+    Collection, Database,
+};
+use poise::futures_util::TryStreamExt;
+
+use crate::{
+    models::DBOrphanedImage,
+    scheduler::JobContext,
+    storage::{self, AvatarStorage},
+};
+
+/// How long an image has to go unused before it's deleted, so quick changes of heart (and
+/// uploads that haven't been saved yet) don't lose anything
+pub const GRACE_PERIOD_DAYS: i64 = 7;
+
+#[derive(Clone, Debug, Default)]
+pub struct GcReport {
+    pub total: usize,
+    pub referenced: usize,
+    /// unused, but still within the grace period
+    pub waiting: usize,
+    /// deleted, or that would have been on a dry run
+    pub deleted: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl GcReport {
+    pub fn describe(&self, dry_run: bool) -> String {
+        format!(
+            "{} images stored, {} in use, {} unused but in their grace period, {} {}{}",
+            self.total,
+            self.referenced,
+            self.waiting,
+            self.deleted.len(),
+            if dry_run {
+                "would be deleted"
+            } else {
+                "deleted"
+            },
+            if self.failed.is_empty() {
+                String::new()
+            } else {
+                format!(", {} failed to delete", self.failed.len())
+            }
+        )
+    }
+}
+
+pub async fn run(ctx: JobContext) -> Result<()> {
+    let report = collect_garbage(&ctx.database, ctx.avatar_storage.as_ref(), false).await?;
+
+    tracing::info!("Avatar GC finished: {}", report.describe(false));
+
+    Ok(())
+}
+
+/// Deletes stored images that no mate (trashed or not), collective, group or backup has used for
+/// the grace period. On a dry run nothing is changed, not even when images were first seen unused
+pub async fn collect_garbage(
+    database: &Database,
+    storage: &dyn AvatarStorage,
+    dry_run: bool,
+) -> Result<GcReport> {
+    let orphans_collection = database.collection::<DBOrphanedImage>("orphaned_images");
+
+    let referenced = get_referenced_keys(database).await?;
+
+    let keys = storage
+        .list()
+        .await
+        .context("Failed to list stored images!")?;
+
+    let orphans = keys
+        .iter()
+        .filter(|key| !referenced.contains(key.as_str()))
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    let marked = orphans_collection
+        .find(doc! {}, None)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let now = Utc::now();
+    let cutoff = now - Duration::days(GRACE_PERIOD_DAYS);
+
+    let mut report = GcReport {
+        total: keys.len(),
+        referenced: keys.len() - orphans.len(),
+        ..Default::default()
+    };
+
+    // anything used again since it was marked gets a fresh grace period next time
+    let unorphaned = marked
+        .iter()
+        .filter(|mark| !orphans.contains(&mark.key))
+        .map(|mark| mark.key.clone())
+        .collect::<Vec<_>>();
+
+    let marked_keys = marked
+        .iter()
+        .filter(|mark| orphans.contains(&mark.key))
+        .map(|mark| (mark.key.as_str(), mark.orphaned_at))
+        .collect::<Vec<_>>();
+
+    let new_orphans = orphans
+        .iter()
+        .filter(|key| !marked_keys.iter().any(|(marked_key, _)| marked_key == key))
+        .map(|key| DBOrphanedImage {
+            key: key.clone(),
+            orphaned_at: now,
+        })
+        .collect::<Vec<_>>();
+
+    report.waiting += new_orphans.len();
+
+    for (key, orphaned_at) in marked_keys {
+        if orphaned_at > cutoff {
+            report.waiting += 1;
+            continue;
+        }
+
+        if dry_run {
+            report.deleted.push(key.to_string());
+            continue;
+        }
+
+        match storage.delete(key).await {
+            Ok(_) => {
+                orphans_collection
+                    .delete_one(doc! { "key": key }, None)
+                    .await?;
+
+                report.deleted.push(key.to_string());
+            }
+            Err(err) => {
+                tracing::warn!("Failed to delete orphaned image {}: {:?}", key, err);
+
+                report.failed.push(key.to_string());
+            }
+        }
+    }
+
+    if !dry_run {
+        if !unorphaned.is_empty() {
+            orphans_collection
+                .delete_many(doc! { "key": { "$in": unorphaned } }, None)
+                .await?;
+        }
+
+        if !new_orphans.is_empty() {
+            orphans_collection.insert_many(new_orphans, None).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Every object key in storage that something still points to
+async fn get_referenced_keys(database: &Database) -> Result<HashSet<String>> {
+    let mut urls = vec![];
+
+    for (collection, field) in [
+        ("mates", "avatar"),
+        ("mates", "banner"),
+        ("collectives", "banner"),
+        ("groups", "icon"),
+        // a tombstone's avatar is the same as its trashed mate's, so it's only kept while they're
+        // in the trash; purging them points the tombstone at the default avatar
+        ("trash", "mate.avatar"),
+        ("trash", "mate.banner"),
+        // backups keep each collection's documents in an array, which `distinct` looks inside of
+        ("backups", "collections.mates.avatar"),
+        ("backups", "collections.mates.banner"),
+        ("backups", "collections.collectives.banner"),
+        ("backups", "collections.groups.icon"),
+        ("backups", "collections.tombstones.avatar"),
+        ("backups", "collections.trash.mate.avatar"),
+        ("backups", "collections.trash.mate.banner"),
+    ] {
+        let collection: Collection<Document> = database.collection(collection);
+
+        urls.extend(
+            collection
+                .distinct(field, doc! {}, None)
+                .await
+                .context("Failed to find which images are in use!")?,
+        );
+    }
+
+    Ok(urls
+        .iter()
+        .filter_map(|url| storage::key_from_url(url.as_str()?))
+        .map(|key| key.to_string())
+        .collect())
+}
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, Utc};
+use mongodb::bson::doc;
+use poise::{
+    futures_util::TryStreamExt,
+    serenity_prelude::{ChannelId, CreateAllowedMentions, CreateMessage, UserId},
+};
+
+use crate::{
+    models::{DBCollective, DBGuild, DBMate, DBUserSettings},
+    scheduler::JobContext,
+    utils::collectives::get_owner_id,
+};
+
+/// Announces today's birthdays in every server with a birthday channel, for the people there who
+/// have opted in
+pub async fn run(ctx: JobContext) -> Result<()> {
+    let mates_collection = ctx.database.collection::<DBMate>("mates");
+    let collectives_collection = ctx.database.collection::<DBCollective>("collectives");
+    let guilds_collection = ctx.database.collection::<DBGuild>("guilds");
+
+    let pattern = birthday_pattern(Utc::now().date_naive());
+
+    // collective owner id -> whose birthday it is
+    let mut birthdays = BTreeMap::<i64, Vec<String>>::new();
+
+    let mut mates = mates_collection
+        .find(doc! { "birthday": { "$regex": &pattern } }, None)
+        .await?;
+
+    while let Some(mate) = mates.try_next().await? {
+        // announcing a birthday shows it to everyone
+        if !mate.is_public || !mate.privacy_settings().birthday.allows(false) {
+            continue;
+        }
+
+        birthdays
+            .entry(mate.user_id)
+            .or_default()
+            .push(mate.display_name.unwrap_or(mate.name));
+    }
+
+    let mut collectives = collectives_collection
+        .find(doc! { "birthday": { "$regex": &pattern } }, None)
+        .await?;
+
+    while let Some(collective) = collectives.try_next().await? {
+        let privacy = collective.privacy_settings();
+
+        if !collective.is_public || !privacy.birthday.allows(false) {
+            continue;
+        }
+
+        birthdays.entry(collective.user_id).or_default().push(
+            collective
+                .name
+                .filter(|_| privacy.name.allows(false))
+                .unwrap_or("the collective".to_string()),
+        );
+    }
+
+    if birthdays.is_empty() {
+        return Ok(());
+    }
+
+    let mut guilds = guilds_collection
+        .find(doc! { "birthday_channel_id": { "$ne": null } }, None)
+        .await?;
+
+    while let Some(guild) = guilds.try_next().await? {
+        // one server having a broken channel shouldn't stop the rest getting announcements
+        if let Err(err) = announce_in_guild(&ctx, &guild, &birthdays).await {
+            tracing::warn!(
+                "Failed to announce birthdays in guild {}: {:?}",
+                guild.id,
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn announce_in_guild(
+    ctx: &JobContext,
+    guild: &DBGuild,
+    birthdays: &BTreeMap<i64, Vec<String>>,
+) -> Result<()> {
+    let Some(channel_id) = guild.birthday_channel_id else {
+        return Ok(());
+    };
+
+    let settings_collection = ctx.database.collection::<DBUserSettings>("settings");
+    let collectives_collection = ctx.database.collection::<DBCollective>("collectives");
+
+    let user_ids = settings_collection
+        .distinct(
+            "user_id",
+            doc! { "guild_id": guild.id, "birthday_announcements": true },
+            None,
+        )
+        .await?;
+
+    // linked accounts share their birthdays, so only announce each collective once
+    let mut owner_ids = BTreeSet::new();
+
+    for user_id in user_ids.iter().filter_map(|user_id| user_id.as_i64()) {
+        let owner_id = get_owner_id(&collectives_collection, UserId::new(user_id as u64)).await?;
+
+        owner_ids.insert(owner_id.get() as i64);
+    }
+
+    let lines = owner_ids
+        .iter()
+        .filter_map(|owner_id| {
+            let names = birthdays.get(owner_id)?;
+
+            Some(format!(
+                "🎂 Happy birthday to {} from <@{}>'s collective!",
+                names
+                    .iter()
+                    .map(|name| format!("**{}**", name.replace('*', "\\*")))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                owner_id
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    for content in split_messages(&lines) {
+        ChannelId::new(channel_id as u64)
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .content(content)
+                    // nobody wants to be pinged at midnight
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Matches birthdays (stored as `YYYY-MM-DD`) on `today`; leap day birthdays are celebrated on
+/// the 28th of February when there isn't one
+fn birthday_pattern(today: NaiveDate) -> String {
+    let is_leap_year = NaiveDate::from_ymd_opt(today.year(), 2, 29).is_some();
+
+    if today.month() == 2 && today.day() == 28 && !is_leap_year {
+        "-02-(28|29)$".to_string()
+    } else {
+        format!("-{}$", today.format("%m-%d"))
+    }
+}
+
+/// Joins lines into as few messages as will fit in Discord's character limit
+fn split_messages(lines: &[String]) -> Vec<String> {
+    let mut messages = vec![];
+    let mut current = String::new();
+
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() + 1 > 2000 {
+            messages.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        messages.push(current);
+    }
+
+    messages
+}
@@ -0,0 +1,3 @@
+pub mod avatar_gc;
+pub mod birthdays;
+pub mod trash;
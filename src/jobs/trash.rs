@@ -0,0 +1,59 @@
+use anyhow::Result;
+use chrono::Utc;
+use mongodb::bson::doc;
+use poise::futures_util::TryStreamExt;
+
+use crate::{
+    models::{DBGroup, DBMateTombstone, DBTrashedMate},
+    scheduler::JobContext,
+    utils::{misc::envvar, trash::purge_date},
+};
+
+/// Purges mates that have been in the trash for too long. Their tombstones stay behind (without
+/// their avatars), so switch logs still know who they were
+pub async fn run(ctx: JobContext) -> Result<()> {
+    let trash_collection = ctx.database.collection::<DBTrashedMate>("trash");
+    let groups_collection = ctx.database.collection::<DBGroup>("groups");
+    let tombstones_collection = ctx.database.collection::<DBMateTombstone>("tombstones");
+
+    let now = Utc::now();
+
+    let mut cursor = trash_collection.find(doc! {}, None).await?;
+
+    let mut expired = vec![];
+
+    while let Some(trashed) = cursor.try_next().await? {
+        if purge_date(&trashed) <= now {
+            expired.push(trashed.id);
+        }
+    }
+
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    groups_collection
+        .update_many(
+            doc! { "mate_ids": { "$in": &expired } },
+            doc! { "$pull": { "mate_ids": { "$in": &expired } } },
+            None,
+        )
+        .await?;
+
+    // otherwise their avatars would be kept forever, for nothing but switch logs
+    tombstones_collection
+        .update_many(
+            doc! { "_id": { "$in": &expired } },
+            doc! { "$set": { "avatar": envvar("DEFAULT_AVATAR_URL") } },
+            None,
+        )
+        .await?;
+
+    trash_collection
+        .delete_many(doc! { "_id": { "$in": &expired } }, None)
+        .await?;
+
+    tracing::info!("Purged {} mates from the trash", expired.len());
+
+    Ok(())
+}
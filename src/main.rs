@@ -1,7 +1,12 @@
+mod api;
 mod commands;
 mod events;
+mod jobs;
 mod models;
+mod multiplex;
 mod pluralkit;
+mod scheduler;
+mod storage;
 mod tupperbox;
 mod utils;
 
@@ -15,10 +20,12 @@ use poise::{
     serenity_prelude::{CacheHttp, Client, Command, FullEvent, GatewayIntents, GuildId},
     Framework,
 };
-use s3::{creds::Credentials, region::Region, Bucket};
 use tokio::{task::JoinSet, time::sleep};
 
-use crate::utils::misc::envvar;
+use crate::{
+    scheduler::{JobContext, Scheduler},
+    utils::misc::envvar,
+};
 
 #[tokio::main]
 async fn main() {
@@ -38,23 +45,19 @@ async fn main() {
 
     let db = client.database(&envvar("DATABASE_NAME"));
 
-    let avatar_bucket = Bucket::new(
-        &envvar("S3_AVATAR_BUCKET"),
-        Region::Custom {
-            region: envvar("S3_REGION"),
-            endpoint: envvar("S3_ENDPOINT"),
-        },
-        Credentials::new(
-            Some(&envvar("S3_KEY_ID")),
-            Some(&envvar("S3_KEY_SECRET")),
-            None,
-            None,
-            None,
-        )
-        .unwrap(),
-    )
-    .unwrap()
-    .with_path_style();
+    if let Err(error) = utils::database::check_replica_set(&db).await {
+        panic!("{:#}", error);
+    }
+
+    utils::database::create_indexes(&db)
+        .await
+        .expect("Failed to create database indexes!");
+
+    let api_db = db.clone();
+    let scheduler_db = db.clone();
+
+    let avatar_storage = storage::from_env();
+    let scheduler_storage = avatar_storage.clone();
 
     let options = poise::FrameworkOptions {
         commands: vec![
@@ -68,11 +71,22 @@ async fn main() {
             commands::mate::switch(),
             commands::edit::edit(),
             commands::info::info(),
+            commands::list::list(),
             commands::import::import(),
             commands::export::export(),
             commands::settings::settings(),
             commands::admin::admin(),
             commands::switch_logs::switch_logs(),
+            commands::front::front(),
+            commands::front::fronters(),
+            commands::link::link(),
+            commands::delegate::delegate(),
+            commands::transfer::transfer(),
+            commands::group::group(),
+            commands::privacy::privacy(),
+            commands::owner::owner(),
+            commands::trash::trash(),
+            commands::backup::backup(),
         ],
         event_handler: |ctx, event, _framework, data| {
             Box::pin(async move {
@@ -125,13 +139,28 @@ async fn main() {
             }
             Ok(Data {
                 database: db,
-                avatar_bucket,
+                avatar_storage,
             })
         })
     }))
     .await
     .unwrap();
 
+    let scheduler = Scheduler::new(JobContext {
+        http: client.http.clone(),
+        database: scheduler_db,
+        avatar_storage: scheduler_storage,
+    })
+    .job("birthdays", Duration::from_secs(60 * 60 * 24), |ctx| {
+        Box::pin(jobs::birthdays::run(ctx))
+    })
+    .job("avatar_gc", Duration::from_secs(60 * 60 * 24), |ctx| {
+        Box::pin(jobs::avatar_gc::run(ctx))
+    })
+    .job("trash", Duration::from_secs(60 * 60 * 24), |ctx| {
+        Box::pin(jobs::trash::run(ctx))
+    });
+
     let mut threads = JoinSet::new();
 
     threads.spawn(async move {
@@ -139,7 +168,10 @@ async fn main() {
     });
 
     threads.spawn(async move {
-        let app = Router::new().route("/health", get(|| async { "( •̀ ω •́ )✧" }));
+        let app = Router::new()
+            .route("/health", get(|| async { "( •̀ ω •́ )✧" }))
+            .merge(api::router(api_db))
+            .merge(storage::router());
 
         axum::Server::bind(&envvar("HEALTH_CHECK_ADDRESS").parse().unwrap())
             .serve(app.into_make_service())
@@ -147,6 +179,8 @@ async fn main() {
             .unwrap();
     });
 
+    threads.spawn(scheduler.run());
+
     while !threads.is_empty() {
         sleep(Duration::from_secs(10)).await;
     }
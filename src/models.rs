@@ -1,7 +1,8 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, oid::ObjectId, Document},
     Collection,
 };
 use orderless::impl_orderless;
@@ -24,6 +25,109 @@ pub struct DBMate {
     pub pronouns: Option<String>,
     pub signature: Option<Signature>,
     pub display_name: Option<String>,
+    /// if unset, every field follows `is_public`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<MatePrivacy>,
+    /// hex code without the `#`, like PluralKit stores it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// the year is 0004 if it isn't known, like PluralKit does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub birthday: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Privacy {
+    #[default]
+    Public,
+    Private,
+}
+
+impl Privacy {
+    pub fn from_public(is_public: bool) -> Self {
+        if is_public {
+            Privacy::Public
+        } else {
+            Privacy::Private
+        }
+    }
+
+    /// Whether someone can see a field with this privacy; collectives can always see their own
+    pub fn allows(&self, is_own: bool) -> bool {
+        is_own || *self == Privacy::Public
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Privacy::Public => "public",
+            Privacy::Private => "private",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatePrivacy {
+    /// hides the mate's real name when they have a display name
+    pub name: Privacy,
+    pub description: Privacy,
+    pub pronouns: Privacy,
+    pub avatar: Privacy,
+    pub birthday: Privacy,
+    /// message counts, when they last proxied, and when they were created
+    pub metadata: Privacy,
+}
+
+impl MatePrivacy {
+    pub fn from_single(privacy: Privacy) -> Self {
+        MatePrivacy {
+            name: privacy,
+            description: privacy,
+            pronouns: privacy,
+            avatar: privacy,
+            birthday: privacy,
+            metadata: privacy,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollectivePrivacy {
+    pub name: Privacy,
+    pub description: Privacy,
+    pub pronouns: Privacy,
+    /// the banner, since collectives don't have an avatar of their own
+    pub avatar: Privacy,
+    pub birthday: Privacy,
+    /// when the collective was created
+    pub metadata: Privacy,
+    pub member_list: Privacy,
+    pub group_list: Privacy,
+    /// who's currently switched in
+    pub front: Privacy,
+    /// switch logs and front stats
+    pub front_history: Privacy,
+}
+
+impl CollectivePrivacy {
+    pub fn from_single(privacy: Privacy) -> Self {
+        CollectivePrivacy {
+            name: privacy,
+            description: privacy,
+            pronouns: privacy,
+            avatar: privacy,
+            birthday: privacy,
+            metadata: privacy,
+            member_list: privacy,
+            group_list: privacy,
+            front: privacy,
+            front_history: privacy,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -59,7 +163,7 @@ impl Signature {
 impl DBMate {
     #[make_orderless(
         public = true,
-        defs(bio = None, prefix = None, postfix = None, pronouns = None, display_name = None, signature = None, id = None),
+        defs(bio = None, prefix = None, postfix = None, pronouns = None, display_name = None, signature = None, id = None, privacy = None, color = None, birthday = None, banner = None),
     )]
     pub fn new(
         user_id: i64,
@@ -74,6 +178,10 @@ impl DBMate {
         display_name: Option<String>,
         signature: Option<Signature>,
         id: Option<ObjectId>,
+        privacy: Option<MatePrivacy>,
+        color: Option<String>,
+        birthday: Option<NaiveDate>,
+        banner: Option<String>,
     ) -> DBMate {
         DBMate {
             user_id,
@@ -88,9 +196,21 @@ impl DBMate {
             display_name,
             signature,
             id,
+            privacy,
+            color,
+            birthday,
+            banner,
         }
     }
 
+    pub fn privacy_settings(&self) -> MatePrivacy {
+        self.privacy
+            .clone()
+            .unwrap_or(MatePrivacy::from_single(Privacy::from_public(
+                self.is_public,
+            )))
+    }
+
     pub async fn edit(
         &mut self,
         collection: Collection<DBMate>,
@@ -102,6 +222,9 @@ impl DBMate {
         publicity: Option<bool>,
         avatar: Option<String>,
         signature: Option<String>,
+        color: Option<String>,
+        birthday: Option<NaiveDate>,
+        banner: Option<String>,
     ) -> Result<()> {
         let current_name = self.name.clone();
 
@@ -141,6 +264,18 @@ impl DBMate {
             self.signature = Some(Signature::parse(signature))
         }
 
+        if color.is_some() {
+            self.color = color
+        }
+
+        if birthday.is_some() {
+            self.birthday = birthday
+        }
+
+        if banner.is_some() {
+            self.banner = banner
+        }
+
         collection
             .find_one_and_replace(
                 doc! { "user_id": self.user_id, "name": current_name },
@@ -153,6 +288,55 @@ impl DBMate {
     }
 }
 
+/// What's left of a mate after they're deleted, so switch logs and proxied messages can still show
+/// who they were
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBMateTombstone {
+    /// the same `_id` the mate had before they were deleted
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: i64,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub avatar: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl DBMateTombstone {
+    pub fn label(&self) -> String {
+        format!(
+            "{} (deleted)",
+            self.display_name.clone().unwrap_or(self.name.clone())
+        )
+    }
+}
+
+/// A deleted mate that can still be restored, until they're purged. Their tombstone is made as
+/// usual when they're trashed, and is left behind when they're purged
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBTrashedMate {
+    /// the same `_id` the mate had before they were deleted
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: i64,
+    pub name: String,
+    /// the mate exactly as they were stored, so restoring them loses nothing
+    pub mate: Document,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBGroup {
+    #[serde(rename = "_id", skip_serializing)]
+    pub id: Option<ObjectId>,
+    pub user_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    /// mates are kept by id so renaming them doesn't drop them from the group
+    pub mate_ids: Vec<ObjectId>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DBCollective {
     #[serde(rename = "_id", skip_serializing)]
@@ -164,6 +348,62 @@ pub struct DBCollective {
     pub pronouns: Option<String>,
     pub collective_tag: Option<String>,
     pub switch_logs: Option<Vec<SwitchLog>>,
+    /// sha256 digest of the token for the public fronters endpoint; if unset, the endpoint is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub front_api_token: Option<String>,
+    /// where to announce switches, if anywhere
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub switch_feed_channel_id: Option<i64>,
+    /// other Discord accounts that share this collective
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_accounts: Option<Vec<i64>>,
+    /// accounts that have been asked to link, but haven't accepted yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_links: Option<Vec<i64>>,
+    /// other people who can manage this collective's messages or proxy as some of its mates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegates: Option<Vec<Delegate>>,
+    /// the account this collective is waiting to be transferred to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_transfer: Option<i64>,
+    /// if unset, every field follows `is_public`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<CollectivePrivacy>,
+    /// hex code without the `#`, like PluralKit stores it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub birthday: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    /// an IANA timezone name, like `Europe/London`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Delegate {
+    pub user_id: i64,
+    pub edit_messages: bool,
+    pub delete_messages: bool,
+    /// the mates they can proxy as, kept by id so renaming a mate doesn't change who can use them
+    #[serde(default)]
+    pub proxy_mate_ids: Vec<ObjectId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DelegatePermission {
+    EditMessages,
+    DeleteMessages,
+}
+
+impl Delegate {
+    pub fn allows(&self, permission: DelegatePermission) -> bool {
+        match permission {
+            DelegatePermission::EditMessages => self.edit_messages,
+            DelegatePermission::DeleteMessages => self.delete_messages,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -178,7 +418,7 @@ pub struct SwitchLog {
 impl DBCollective {
     #[make_orderless(
         public = true,
-        defs(name = None, bio = None, pronouns = None, collective_tag = None, id = None, switch_logs = None),
+        defs(name = None, bio = None, pronouns = None, collective_tag = None, id = None, switch_logs = None, front_api_token = None, switch_feed_channel_id = None, linked_accounts = None, pending_links = None, delegates = None, pending_transfer = None, privacy = None, color = None, birthday = None, banner = None, timezone = None),
     )]
     pub fn new(
         user_id: i64,
@@ -189,6 +429,17 @@ impl DBCollective {
         collective_tag: Option<String>,
         id: Option<ObjectId>,
         switch_logs: Option<Vec<SwitchLog>>,
+        front_api_token: Option<String>,
+        switch_feed_channel_id: Option<i64>,
+        linked_accounts: Option<Vec<i64>>,
+        pending_links: Option<Vec<i64>>,
+        delegates: Option<Vec<Delegate>>,
+        pending_transfer: Option<i64>,
+        privacy: Option<CollectivePrivacy>,
+        color: Option<String>,
+        birthday: Option<NaiveDate>,
+        banner: Option<String>,
+        timezone: Option<String>,
     ) -> DBCollective {
         DBCollective {
             user_id,
@@ -199,6 +450,17 @@ impl DBCollective {
             collective_tag,
             id,
             switch_logs,
+            front_api_token,
+            switch_feed_channel_id,
+            linked_accounts,
+            pending_links,
+            delegates,
+            pending_transfer,
+            privacy,
+            color,
+            birthday,
+            banner,
+            timezone,
         }
     }
 
@@ -210,6 +472,10 @@ impl DBCollective {
         pronouns: Option<String>,
         is_public: Option<bool>,
         collective_tag: Option<String>,
+        color: Option<String>,
+        birthday: Option<NaiveDate>,
+        banner: Option<String>,
+        timezone: Option<String>,
     ) -> Result<()> {
         if name.is_some() {
             self.name = name
@@ -236,6 +502,22 @@ impl DBCollective {
             }
         }
 
+        if color.is_some() {
+            self.color = color
+        }
+
+        if birthday.is_some() {
+            self.birthday = birthday
+        }
+
+        if banner.is_some() {
+            self.banner = banner
+        }
+
+        if timezone.is_some() {
+            self.timezone = timezone
+        }
+
         collection
             .find_one_and_replace(doc! { "user_id": self.user_id }, self, None)
             .await?
@@ -243,6 +525,31 @@ impl DBCollective {
 
         Ok(())
     }
+
+    pub fn privacy_settings(&self) -> CollectivePrivacy {
+        self.privacy
+            .clone()
+            .unwrap_or(CollectivePrivacy::from_single(Privacy::from_public(
+                self.is_public,
+            )))
+    }
+
+    /// The collective's timezone, or UTC if they haven't set one
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+            .as_deref()
+            .and_then(|timezone| timezone.parse().ok())
+            .unwrap_or(Tz::UTC)
+    }
+
+    /// Every Discord account that uses this collective, starting with the one that owns it
+    pub fn accounts(&self) -> Vec<i64> {
+        let mut accounts = vec![self.user_id];
+
+        accounts.extend(self.linked_accounts.clone().unwrap_or_default());
+
+        accounts
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -250,6 +557,8 @@ pub struct DBGuild {
     pub id: i64,
     pub proxy_logs_channel_id: Option<i64>,
     pub allowlist_role: Option<i64>,
+    /// where to announce the birthdays of people who've opted in, if anywhere
+    pub birthday_channel_id: Option<i64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -264,6 +573,11 @@ pub struct DBMessage {
     pub user_id: u64,
     pub message_id: u64,
     pub mate_name: Option<String>,
+    /// missing for messages proxied before this was kept, which only have `mate_name` to go off of
+    pub mate_id: Option<ObjectId>,
+    /// whoever actually sent it, which isn't the collective's owner for linked accounts and
+    /// delegates. Missing for messages proxied before this was kept
+    pub sender_id: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -280,6 +594,12 @@ pub struct DBUserSettings {
     pub guild_id: Option<i64>,
     pub autoproxy: Option<AutoproxySettings>,
     pub regex_sed_editing: Option<bool>,
+    /// (guild only) whether to change the user's nickname to whoever's switched in
+    pub fronter_nickname: Option<bool>,
+    /// (guild only) the nickname to go back to when nobody's switched in
+    pub original_nickname: Option<String>,
+    /// (guild only) whether to announce this collective's birthdays in the guild's birthday channel
+    pub birthday_announcements: Option<bool>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -287,3 +607,31 @@ pub enum Latch {
     Guild(Option<String>),
     Global(Option<String>),
 }
+
+/// When a scheduled job last ran, so restarting doesn't run it again early
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBJobRun {
+    pub name: String,
+    pub last_run: DateTime<Utc>,
+}
+
+/// A stored image that nothing used the last time it was checked
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBOrphanedImage {
+    pub key: String,
+    pub orphaned_at: DateTime<Utc>,
+}
+
+/// A snapshot of everything a collective owned, taken before something destructive happens to it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DBBackup {
+    #[serde(rename = "_id", skip_serializing)]
+    pub id: Option<ObjectId>,
+    pub user_id: i64,
+    pub version: i32,
+    /// what was about to happen, ie "before /reset"
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    /// collection name -> the user's documents in it, stored raw
+    pub collections: Document,
+}
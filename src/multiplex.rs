@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use poise::serenity_prelude::UserId;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    DBCollective, DBGroup, DBMate, DBMateTombstone, DBMessage, DBTrashedMate, DBUserSettings,
+    SwitchLog,
+};
+
+/// Marks an export as ours, so it can't be mistaken for another bot's
+pub const EXPORT_FORMAT: &str = "multiplex";
+/// Bumped whenever the export schema changes in a way older versions can't read
+pub const EXPORT_VERSION: i32 = 1;
+
+/// Everything a collective owns, stored as close to how it's kept in the database as possible, so
+/// nothing is lost in a round trip. Mates keep their ids, so switch logs and groups (which refer
+/// to mates by id) can be pointed at whatever ids they get when they're imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiplexExport {
+    pub format: String,
+    pub version: i32,
+    pub exported_at: DateTime<Utc>,
+    pub collective: DBCollective,
+    pub mates: Vec<ExportedMate>,
+    #[serde(default)]
+    pub groups: Vec<DBGroup>,
+    #[serde(default)]
+    pub tombstones: Vec<DBMateTombstone>,
+    #[serde(default)]
+    pub trash: Vec<DBTrashedMate>,
+    /// the exporting account's own settings, globally and per guild
+    #[serde(default)]
+    pub settings: Vec<DBUserSettings>,
+    /// proxied messages, only if they were asked for. They're never imported, since there's no way
+    /// to check they were really sent by this collective
+    #[serde(default)]
+    pub messages: Vec<DBMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedMate {
+    /// the mate's id when they were exported; switch logs and groups in the export use it
+    pub id: ObjectId,
+    #[serde(flatten)]
+    pub mate: DBMate,
+}
+
+impl MultiplexExport {
+    pub fn new(
+        collective: DBCollective,
+        mates: Vec<DBMate>,
+        groups: Vec<DBGroup>,
+        tombstones: Vec<DBMateTombstone>,
+        trash: Vec<DBTrashedMate>,
+        settings: Vec<DBUserSettings>,
+        messages: Vec<DBMessage>,
+    ) -> Self {
+        MultiplexExport {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            collective,
+            mates: mates
+                .into_iter()
+                // impossible for the id to not exist
+                .map(|mate| ExportedMate {
+                    id: mate.id.unwrap(),
+                    mate,
+                })
+                .collect(),
+            groups,
+            tombstones,
+            trash,
+            settings,
+            messages,
+        }
+    }
+
+    /// Reads an export, with a useful error if it's from a different version (or isn't ours)
+    pub fn parse(content: &str) -> Result<Self> {
+        let export = serde_json::from_str::<serde_json::Value>(content)
+            .context("That file isn't valid JSON!")?;
+
+        if export.get("format").and_then(|format| format.as_str()) != Some(EXPORT_FORMAT) {
+            bail!(
+                "That doesn't look like a Multiplex export! (Exports made before Multiplex had \
+                 its own format, and ones made with `/export format: PluralKit`, can be imported \
+                 with `/import pluralkit` instead.)"
+            );
+        }
+
+        let version = export
+            .get("version")
+            .and_then(|version| version.as_i64())
+            .context("That export doesn't say which version it is!")?;
+
+        if version > EXPORT_VERSION as i64 {
+            bail!(
+                "That export was made by a newer version of Multiplex (version {}, this is \
+                 version {}), so it can't be imported here.",
+                version,
+                EXPORT_VERSION
+            );
+        }
+
+        serde_json::from_value(export).context("Failed to read that export!")
+    }
+
+    /// The collective, without anything that only makes sense for the account it came from.
+    /// Switch logs are handled separately, since they need remapping first
+    pub fn to_collective(&self, user_id: UserId) -> DBCollective {
+        DBCollective {
+            id: None,
+            user_id: user_id.get() as i64,
+            switch_logs: None,
+            // other accounts have to accept being linked again
+            linked_accounts: None,
+            pending_links: None,
+            pending_transfer: None,
+            // the channel was checked when it was set, for whoever set it
+            switch_feed_channel_id: None,
+            // an export shouldn't be able to hand out access to your collective
+            delegates: None,
+            front_api_token: None,
+            ..self.collective.clone()
+        }
+    }
+
+    pub fn to_mates(&self, user_id: UserId) -> Vec<DBMate> {
+        self.mates
+            .iter()
+            .map(|exported| DBMate {
+                id: None,
+                user_id: user_id.get() as i64,
+                // set again from the switch logs
+                autoproxy: false,
+                ..exported.mate.clone()
+            })
+            .collect()
+    }
+
+    /// Exported mate id -> the id they have now, using already-imported mates
+    fn mate_ids(&self, mates: &[DBMate]) -> HashMap<ObjectId, ObjectId> {
+        self.mates
+            .iter()
+            .filter_map(|exported| {
+                let mate = mates.iter().find(|mate| mate.name == exported.mate.name)?;
+
+                Some((exported.id, mate.id?))
+            })
+            .collect()
+    }
+
+    /// The export's switch logs, pointed at already-imported mates. Ids that aren't mates (ie
+    /// tombstones, which keep their ids) are left alone
+    pub fn to_switch_logs(&self, mates: &[DBMate]) -> Vec<SwitchLog> {
+        let mate_ids = self.mate_ids(mates);
+        let remap = |id: Option<ObjectId>| id.map(|id| mate_ids.get(&id).copied().unwrap_or(id));
+
+        self.collective
+            .switch_logs
+            .iter()
+            .flatten()
+            .map(|log| SwitchLog {
+                mate_id: remap(log.mate_id),
+                previous_mate_id: remap(log.previous_mate_id),
+                ..log.clone()
+            })
+            .collect()
+    }
+
+    /// The export's groups, pointed at already-imported mates
+    pub fn to_groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        let mate_ids = self.mate_ids(mates);
+
+        self.groups
+            .iter()
+            .map(|group| DBGroup {
+                id: None,
+                user_id: user_id.get() as i64,
+                mate_ids: group
+                    .mate_ids
+                    .iter()
+                    .filter_map(|mate_id| mate_ids.get(mate_id).copied())
+                    .collect(),
+                ..group.clone()
+            })
+            .collect()
+    }
+
+    pub fn to_tombstones(&self, user_id: UserId) -> Vec<DBMateTombstone> {
+        self.tombstones
+            .iter()
+            .map(|tombstone| DBMateTombstone {
+                user_id: user_id.get() as i64,
+                ..tombstone.clone()
+            })
+            .collect()
+    }
+
+    pub fn to_trash(&self, user_id: UserId) -> Vec<DBTrashedMate> {
+        self.trash
+            .iter()
+            .map(|trashed| {
+                let mut mate = trashed.mate.clone();
+                mate.insert("user_id", user_id.get() as i64);
+
+                DBTrashedMate {
+                    user_id: user_id.get() as i64,
+                    mate,
+                    ..trashed.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Settings belong to a single account, so they go to whoever's importing
+    pub fn to_settings(&self, account_id: UserId) -> Vec<DBUserSettings> {
+        self.settings
+            .iter()
+            .map(|settings| DBUserSettings {
+                user_id: account_id.get(),
+                ..settings.clone()
+            })
+            .collect()
+    }
+
+    /// Everything in the export that can't be imported as it is, for the import preview
+    pub fn dropped(&self) -> Vec<String> {
+        let mut dropped = vec![];
+
+        if self.collective.switch_feed_channel_id.is_some() {
+            dropped.push(
+                "Your switch feed channel (set it again with `/settings switch_feed`)".to_string(),
+            );
+        }
+
+        if let Some(delegates) = self
+            .collective
+            .delegates
+            .as_ref()
+            .filter(|delegates| !delegates.is_empty())
+        {
+            dropped.push(format!(
+                "{} delegates (add them again with `/delegate set`)",
+                delegates.len()
+            ));
+        }
+
+        if self.collective.front_api_token.is_some() {
+            dropped.push("Your fronters link (make a new one with `/front api`)".to_string());
+        }
+
+        if self.collective.pending_transfer.is_some() {
+            dropped.push("A pending transfer".to_string());
+        }
+
+        if !self.messages.is_empty() {
+            dropped.push(format!(
+                "{} proxied messages (there's no way to check they're yours)",
+                self.messages.len()
+            ));
+        }
+
+        dropped
+    }
+}
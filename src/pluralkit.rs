@@ -1,12 +1,21 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use mongodb::bson::oid::ObjectId;
 use poise::serenity_prelude::UserId;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use crate::{
-    models::{DBCollective, DBCollective__new, DBMate, DBMate__new},
-    utils::misc::envvar,
+    models::{
+        CollectivePrivacy, DBCollective, DBCollective__new, DBGroup, DBMate, DBMate__new,
+        MatePrivacy, Privacy, SwitchLog,
+    },
+    utils::{
+        collectives::{normalize_switch_logs, SWITCH_LOG_LIMIT},
+        misc::envvar,
+        time::parse_timezone,
+    },
 };
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,7 +38,8 @@ pub struct PluralkitExport<'a> {
     pub accounts: Vec<i64>,
     #[serde(borrow)]
     pub members: Vec<Member<'a>>,
-    pub groups: Vec<Value>,
+    #[serde(borrow)]
+    pub groups: Vec<Group<'a>>,
     pub switches: Vec<Switch>,
 }
 
@@ -37,38 +47,238 @@ impl<'a> PluralkitExport<'a> {
     pub fn to_collective(&self, user_id: UserId) -> Result<DBCollective> {
         Ok(DBCollective__new! {
             user_id = user_id.get() as i64,
-            is_public = !self.privacy.is_private()?,
+            // there's nothing to see if every field is private
+            is_public = !self.privacy.is_all_private(),
             name = self.name.clone(),
             bio = self.description.clone(),
             pronouns = self.pronouns.clone(),
             collective_tag = self.tag.clone(),
+            privacy = Some(self.privacy.to_collective_privacy()),
+            color = self.color.clone(),
+            banner = self.banner.clone(),
+            // PK only lets you set valid timezones, but it's not worth failing the import over
+            timezone = parse_timezone(&self.config.timezone)
+                .ok()
+                .map(|timezone| timezone.name().to_string()),
         })
     }
+
+    /// Converts PK's switches into switch logs, using already-imported mates. Multiplex only
+    /// tracks one mate being switched in at a time, so only the first member of a switch is kept
+    pub fn to_switch_logs(&self, mates: &[DBMate]) -> Vec<SwitchLog> {
+        let mate_ids = self.mate_ids(mates);
+
+        let mut switch_logs = self
+            .switches
+            .iter()
+            .filter_map(|switch| {
+                let mate_id = match switch.members.first() {
+                    // skip switches to members that didn't make it into the import
+                    Some(member) => Some(*mate_ids.get(member.as_str())?),
+                    None => None,
+                };
+
+                Some(SwitchLog {
+                    date: switch.timestamp,
+                    mate_id,
+                    previous_mate_id: None,
+                    unswitch: mate_id.is_none(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        normalize_switch_logs(&mut switch_logs);
+
+        switch_logs.truncate(SWITCH_LOG_LIMIT);
+
+        switch_logs
+    }
+
+    /// Converts PK's groups into groups, using already-imported mates
+    pub fn to_groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        let mate_ids = self.mate_ids(mates);
+
+        self.groups
+            .iter()
+            .map(|group| DBGroup {
+                id: None,
+                user_id: user_id.get() as i64,
+                name: group.name.clone(),
+                description: group.description.clone(),
+                icon: group.icon.clone(),
+                mate_ids: group
+                    .members
+                    .iter()
+                    .filter_map(|member| mate_ids.get(member.as_str()).copied())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Everything in the export that Multiplex has nowhere to put, for the import preview
+    pub fn dropped(&self) -> Vec<String> {
+        let mut dropped = vec![];
+
+        let extra_proxy_tags = self
+            .members
+            .iter()
+            .filter(|member| member.proxy_tags.len() > 1)
+            .count();
+        if extra_proxy_tags > 0 {
+            dropped.push(format!(
+                "Extra proxy tags of {} members (only the first is kept)",
+                extra_proxy_tags
+            ));
+        }
+
+        let bad_birthdays = self
+            .members
+            .iter()
+            .filter(|member| {
+                member.birthday.as_deref().is_some_and(|birthday| {
+                    NaiveDate::parse_from_str(birthday, "%Y-%m-%d").is_err()
+                })
+            })
+            .count();
+        if bad_birthdays > 0 {
+            dropped.push(format!("{} birthdays that couldn't be read", bad_birthdays));
+        }
+
+        let shared_switches = self
+            .switches
+            .iter()
+            .filter(|switch| switch.members.len() > 1)
+            .count();
+        if shared_switches > 0 {
+            dropped.push(format!(
+                "Everyone but the first member of {} switches with more than one member",
+                shared_switches
+            ));
+        }
+
+        if self.switches.len() > SWITCH_LOG_LIMIT {
+            dropped.push(format!(
+                "The oldest {} switches (only the last {} are kept)",
+                self.switches.len() - SWITCH_LOG_LIMIT,
+                SWITCH_LOG_LIMIT
+            ));
+        }
+
+        if parse_timezone(&self.config.timezone).is_err() {
+            dropped.push(format!(
+                "The timezone `{}`, which isn't recognised",
+                self.config.timezone
+            ));
+        }
+
+        if self.avatar_url.is_some() {
+            dropped.push("The system avatar".to_string());
+        }
+
+        let group_extras = self
+            .groups
+            .iter()
+            .filter(|group| {
+                group.display_name.is_some() || group.banner.is_some() || group.color.is_some()
+            })
+            .count();
+        if group_extras > 0 {
+            dropped.push(format!(
+                "Display names, banners and colours of {} groups",
+                group_extras
+            ));
+        }
+
+        dropped
+    }
+
+    /// Maps both the short ids and uuids of members to the ids of the mates they were imported as.
+    /// Empty ones are skipped, since our own exports leave the uuid blank
+    fn mate_ids(&self, mates: &[DBMate]) -> HashMap<&str, ObjectId> {
+        self.members
+            .iter()
+            .filter_map(|member| {
+                let mate_id = mates.iter().find(|mate| mate.name == member.name)?.id?;
+
+                Some([
+                    (member.id.as_str(), mate_id),
+                    (member.uuid.as_str(), mate_id),
+                ])
+            })
+            .flatten()
+            .filter(|(id, _)| !id.is_empty())
+            .collect()
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SystemPrivacy<'a> {
+    /// only in newer exports
+    #[serde(default)]
+    pub name_privacy: &'a str,
     pub description_privacy: &'a str,
     pub pronoun_privacy: &'a str,
     pub member_list_privacy: &'a str,
     pub group_list_privacy: &'a str,
     pub front_privacy: &'a str,
     pub front_history_privacy: &'a str,
+    #[serde(default)]
+    pub avatar_privacy: &'a str,
+    #[serde(default)]
+    pub banner_privacy: &'a str,
 }
 
 impl<'a> SystemPrivacy<'a> {
-    pub fn is_private(&self) -> Result<bool> {
-        Ok(serde_json::to_string(&self)?.contains("\"private\""))
+    pub fn to_collective_privacy(&self) -> CollectivePrivacy {
+        CollectivePrivacy {
+            name: parse_privacy(self.name_privacy),
+            description: parse_privacy(self.description_privacy),
+            pronouns: parse_privacy(self.pronoun_privacy),
+            // collectives only have a banner, so it's hidden if either is
+            avatar: if parse_privacy(self.avatar_privacy) == Privacy::Private {
+                Privacy::Private
+            } else {
+                parse_privacy(self.banner_privacy)
+            },
+            // PK systems don't have these
+            birthday: Privacy::Public,
+            metadata: Privacy::Public,
+            member_list: parse_privacy(self.member_list_privacy),
+            group_list: parse_privacy(self.group_list_privacy),
+            front: parse_privacy(self.front_privacy),
+            front_history: parse_privacy(self.front_history_privacy),
+        }
     }
 
-    pub fn create_from_single(privacy: &'a str) -> Self {
-        SystemPrivacy::<'a> {
-            description_privacy: privacy,
-            pronoun_privacy: privacy,
-            member_list_privacy: privacy,
-            group_list_privacy: privacy,
-            front_privacy: privacy,
-            front_history_privacy: privacy,
+    pub fn is_all_private(&self) -> bool {
+        [
+            self.name_privacy,
+            self.description_privacy,
+            self.pronoun_privacy,
+            self.member_list_privacy,
+            self.group_list_privacy,
+            self.front_privacy,
+            self.front_history_privacy,
+            self.avatar_privacy,
+            self.banner_privacy,
+        ]
+        .iter()
+        // older exports don't have some of these
+        .filter(|privacy| !privacy.is_empty())
+        .all(|privacy| *privacy == "private")
+    }
+
+    pub fn from_collective_privacy(privacy: &CollectivePrivacy) -> Self {
+        SystemPrivacy {
+            name_privacy: privacy.name.as_str(),
+            description_privacy: privacy.description.as_str(),
+            pronoun_privacy: privacy.pronouns.as_str(),
+            member_list_privacy: privacy.member_list.as_str(),
+            group_list_privacy: privacy.group_list.as_str(),
+            front_privacy: privacy.front.as_str(),
+            front_history_privacy: privacy.front_history.as_str(),
+            avatar_privacy: privacy.avatar.as_str(),
+            banner_privacy: privacy.avatar.as_str(),
         }
     }
 }
@@ -147,11 +357,27 @@ impl<'a> Member<'a> {
                 postfix = proxy_tags.suffix.clone(),
                 pronouns = self.pronouns.clone(),
                 display_name = self.display_name.clone(),
-                is_public = !self.privacy.is_private()?,
+                is_public = self.privacy.visibility != "private",
+                privacy = Some(self.privacy.to_mate_privacy()),
+                color = self.color.clone(),
+                birthday = self
+                    .birthday
+                    .as_deref()
+                    .and_then(|birthday| NaiveDate::parse_from_str(birthday, "%Y-%m-%d").ok()),
+                banner = self.banner.clone(),
         })
     }
 }
 
+/// PK only has public and private, but anything that isn't explicitly private is treated as public
+fn parse_privacy(privacy: &str) -> Privacy {
+    if privacy == "private" {
+        Privacy::Private
+    } else {
+        Privacy::Public
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProxyTag {
     pub prefix: Option<String>,
@@ -170,18 +396,70 @@ pub struct MemberPrivacy<'a> {
 }
 
 impl<'a> MemberPrivacy<'a> {
-    pub fn is_private(&self) -> Result<bool> {
-        Ok(serde_json::to_string(&self)?.contains("\"private\""))
+    pub fn to_mate_privacy(&self) -> MatePrivacy {
+        MatePrivacy {
+            name: parse_privacy(self.name_privacy),
+            description: parse_privacy(self.description_privacy),
+            pronouns: parse_privacy(self.pronoun_privacy),
+            avatar: parse_privacy(self.avatar_privacy),
+            birthday: parse_privacy(self.birthday_privacy),
+            metadata: parse_privacy(self.metadata_privacy),
+        }
     }
 
+    pub fn from_mate(mate: &DBMate) -> Self {
+        let privacy = mate.privacy_settings();
+
+        MemberPrivacy {
+            visibility: Privacy::from_public(mate.is_public).as_str(),
+            name_privacy: privacy.name.as_str(),
+            description_privacy: privacy.description.as_str(),
+            birthday_privacy: privacy.birthday.as_str(),
+            pronoun_privacy: privacy.pronouns.as_str(),
+            avatar_privacy: privacy.avatar.as_str(),
+            metadata_privacy: privacy.metadata.as_str(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group<'a> {
+    pub id: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub banner: Option<String>,
+    pub color: Option<String>,
+    /// missing from older exports and from tupperbox, where everything's public anyway
+    #[serde(borrow, default)]
+    pub privacy: GroupPrivacy<'a>,
+    /// the ids of the members in the group
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroupPrivacy<'a> {
+    pub name_privacy: &'a str,
+    pub description_privacy: &'a str,
+    pub icon_privacy: &'a str,
+    pub list_privacy: &'a str,
+    pub metadata_privacy: &'a str,
+    pub visibility: &'a str,
+}
+
+impl<'a> GroupPrivacy<'a> {
     pub fn create_from_single(privacy: &'a str) -> Self {
-        MemberPrivacy::<'a> {
+        GroupPrivacy::<'a> {
+            name_privacy: privacy,
             description_privacy: privacy,
-            pronoun_privacy: privacy,
-            avatar_privacy: privacy,
-            birthday_privacy: privacy,
+            icon_privacy: privacy,
+            list_privacy: privacy,
             metadata_privacy: privacy,
-            name_privacy: privacy,
             visibility: privacy,
         }
     }
@@ -189,6 +467,6 @@ impl<'a> MemberPrivacy<'a> {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Switch {
-    pub timestamp: String,
+    pub timestamp: DateTime<Utc>,
     pub members: Vec<String>,
 }
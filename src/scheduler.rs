@@ -0,0 +1,134 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::{self, doc},
+    options::UpdateOptions,
+    Collection, Database,
+};
+use poise::{serenity_prelude::Http, BoxFuture};
+use tokio::time::sleep;
+
+use crate::{models::DBJobRun, storage::SharedAvatarStorage};
+
+/// How often to check whether any jobs are due
+const TICK: Duration = Duration::from_secs(60);
+
+/// Everything a job gets to work with
+#[derive(Clone)]
+pub struct JobContext {
+    pub http: Arc<Http>,
+    pub database: Database,
+    pub avatar_storage: SharedAvatarStorage,
+}
+
+pub type JobFn = fn(JobContext) -> BoxFuture<'static, Result<()>>;
+
+struct Job {
+    name: &'static str,
+    every: Duration,
+    run: JobFn,
+}
+
+/// Runs jobs once every so often. Runs are lined up with the Unix epoch (so daily jobs run just
+/// after midnight UTC), and when each job last ran is saved so restarts don't run it twice
+pub struct Scheduler {
+    context: JobContext,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(context: JobContext) -> Self {
+        Scheduler {
+            context,
+            jobs: vec![],
+        }
+    }
+
+    pub fn job(mut self, name: &'static str, every: Duration, run: JobFn) -> Self {
+        self.jobs.push(Job { name, every, run });
+        self
+    }
+
+    pub async fn run(self) {
+        let runs_collection = self.context.database.collection::<DBJobRun>("jobs");
+
+        let mut last_runs = HashMap::new();
+
+        loop {
+            let now = Utc::now();
+
+            for job in self.jobs.iter() {
+                let last_run = match last_runs.get(job.name) {
+                    Some(last_run) => Some(*last_run),
+                    None => get_last_run(&runs_collection, job.name)
+                        .await
+                        .unwrap_or_else(|err| {
+                            tracing::error!(
+                                "Failed to get last run of job {}: {:?}",
+                                job.name,
+                                err
+                            );
+                            // better to skip a run than to run twice
+                            Some(now)
+                        }),
+                };
+
+                if let Some(last_run) =
+                    last_run.filter(|last_run| !is_due(*last_run, now, job.every))
+                {
+                    last_runs.insert(job.name, last_run);
+                    continue;
+                }
+
+                // saved before running, so a job that fails halfway doesn't redo what it did
+                last_runs.insert(job.name, now);
+                if let Err(err) = save_last_run(&runs_collection, job.name, now).await {
+                    tracing::error!("Failed to save last run of job {}: {:?}", job.name, err);
+                }
+
+                tracing::info!("Running job {}", job.name);
+
+                if let Err(err) = (job.run)(self.context.clone()).await {
+                    tracing::error!("Job {} failed: {:?}", job.name, err);
+                }
+            }
+
+            sleep(TICK).await;
+        }
+    }
+}
+
+/// Whether `now` is in a later window of length `every` than `last_run`
+fn is_due(last_run: DateTime<Utc>, now: DateTime<Utc>, every: Duration) -> bool {
+    let every = every.as_secs().max(1) as i64;
+
+    last_run.timestamp().div_euclid(every) < now.timestamp().div_euclid(every)
+}
+
+async fn get_last_run(
+    collection: &Collection<DBJobRun>,
+    name: &str,
+) -> Result<Option<DateTime<Utc>>> {
+    Ok(collection
+        .find_one(doc! { "name": name }, None)
+        .await?
+        .map(|run| run.last_run))
+}
+
+async fn save_last_run(
+    collection: &Collection<DBJobRun>,
+    name: &str,
+    last_run: DateTime<Utc>,
+) -> Result<()> {
+    collection
+        .update_one(
+            doc! { "name": name },
+            doc! { "$set": { "last_run": bson::to_bson(&last_run)? } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use std::{env, path::PathBuf, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use reqwest::Url;
+use s3::{creds::Credentials, region::Region, Bucket};
+
+use crate::utils::misc::envvar;
+
+/// Somewhere to keep avatars (and banners, and group icons). Keys are bare file names like
+/// `<sha256>.png`; the public URL of a key is always `PUBLIC_AVATAR_URL/<key>`
+#[async_trait]
+pub trait AvatarStorage: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<()>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Every key currently stored
+    async fn list(&self) -> Result<Vec<String>>;
+}
+
+pub type SharedAvatarStorage = Arc<dyn AvatarStorage>;
+
+/// Picks a backend based on `AVATAR_STORAGE` (`s3`, the default, or `filesystem`)
+pub fn from_env() -> SharedAvatarStorage {
+    match env::var("AVATAR_STORAGE").as_deref() {
+        Ok("filesystem") => Arc::new(FilesystemStorage::new(envvar("AVATAR_DIRECTORY"))),
+        Ok("s3") | Err(_) => Arc::new(S3Storage::from_env()),
+        Ok(other) => panic!(
+            "Unknown AVATAR_STORAGE {:?}; it should be either \"s3\" or \"filesystem\"",
+            other
+        ),
+    }
+}
+
+pub fn public_url(key: &str) -> String {
+    format!("{}/{}", envvar("PUBLIC_AVATAR_URL"), key)
+}
+
+/// The inverse of [`public_url`], for URLs that point at our own storage
+pub fn key_from_url(url: &str) -> Option<&str> {
+    url.strip_prefix(&format!("{}/", envvar("PUBLIC_AVATAR_URL")))
+        .filter(|key| is_valid_key(key))
+}
+
+/// Keys are always flat file names, so nothing can escape the avatar directory
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '.' || char == '-' || char == '_')
+}
+
+pub struct S3Storage {
+    bucket: Bucket,
+}
+
+impl S3Storage {
+    pub fn from_env() -> Self {
+        let bucket = Bucket::new(
+            &envvar("S3_AVATAR_BUCKET"),
+            Region::Custom {
+                region: envvar("S3_REGION"),
+                endpoint: envvar("S3_ENDPOINT"),
+            },
+            Credentials::new(
+                Some(&envvar("S3_KEY_ID")),
+                Some(&envvar("S3_KEY_SECRET")),
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .with_path_style();
+
+        S3Storage { bucket }
+    }
+}
+
+#[async_trait]
+impl AvatarStorage for S3Storage {
+    async fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<()> {
+        self.bucket
+            .put_object_with_content_type(format!("/{}", key), data, content_type)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.bucket.delete_object(format!("/{}", key)).await?;
+
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        Ok(self
+            .bucket
+            .list(String::new(), None)
+            .await?
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| object.key)
+            .collect())
+    }
+}
+
+/// Keeps avatars in a local directory, served by our own HTTP server (see [`router`]), so
+/// self-hosting doesn't need an S3 endpoint
+pub struct FilesystemStorage {
+    directory: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        let directory = directory.into();
+
+        std::fs::create_dir_all(&directory).expect("Failed to create the avatar directory!");
+
+        FilesystemStorage { directory }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if !is_valid_key(key) {
+            bail!("Invalid avatar key {:?}", key);
+        }
+
+        Ok(self.directory.join(key))
+    }
+}
+
+#[async_trait]
+impl AvatarStorage for FilesystemStorage {
+    async fn put(&self, key: &str, data: &[u8], _content_type: &str) -> Result<()> {
+        let path = self.path(key)?;
+
+        // write somewhere else first, so nobody gets served half an image
+        let temp_path = self.directory.join(format!(".{}.tmp", key));
+
+        tokio::fs::write(&temp_path, data)
+            .await
+            .context("Failed to write avatar!")?;
+        tokio::fs::rename(&temp_path, &path)
+            .await
+            .context("Failed to write avatar!")?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut entries = tokio::fs::read_dir(&self.directory).await?;
+        let mut keys = vec![];
+
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(key) = entry.file_name().to_str().filter(|key| is_valid_key(key)) {
+                keys.push(key.to_string());
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Serves avatars under `PUBLIC_AVATAR_URL`'s path when they're kept on the filesystem; with S3,
+/// the bucket serves them itself and this does nothing
+pub fn router() -> Router {
+    if env::var("AVATAR_STORAGE").as_deref() != Ok("filesystem") {
+        return Router::new();
+    }
+
+    let public_url =
+        Url::parse(&envvar("PUBLIC_AVATAR_URL")).expect("PUBLIC_AVATAR_URL isn't a valid URL!");
+
+    Router::new()
+        .route(
+            &format!("{}/:key", public_url.path().trim_end_matches('/')),
+            get(serve_avatar),
+        )
+        .with_state(PathBuf::from(envvar("AVATAR_DIRECTORY")))
+}
+
+async fn serve_avatar(
+    State(directory): State<PathBuf>,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !is_valid_key(&key) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let data = tokio::fs::read(directory.join(&key))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let content_type = match key.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            // keys are content hashes, so they never change
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        data,
+    ))
+}
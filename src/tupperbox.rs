@@ -3,13 +3,68 @@ use poise::serenity_prelude::UserId;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::{DBMate, DBMate__new},
+    models::{DBGroup, DBMate, DBMate__new},
     utils::misc::envvar,
 };
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TupperboxExport {
     pub tuppers: Vec<Tupper>,
+    #[serde(default)]
+    pub groups: Vec<TupperboxGroup>,
+}
+
+impl TupperboxExport {
+    /// Converts Tupperbox's groups into groups, using already-imported mates
+    pub fn to_groups(&self, user_id: UserId, mates: &[DBMate]) -> Vec<DBGroup> {
+        self.groups
+            .iter()
+            .map(|group| DBGroup {
+                id: None,
+                user_id: user_id.get() as i64,
+                name: group.name.clone(),
+                description: group.description.clone(),
+                icon: None,
+                mate_ids: self
+                    .tuppers
+                    .iter()
+                    .filter(|tupper| tupper.group_id == Some(group.id))
+                    .filter_map(|tupper| mates.iter().find(|mate| mate.name == tupper.name)?.id)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Everything in the export that Multiplex has nowhere to put, for the import preview
+    pub fn dropped(&self) -> Vec<String> {
+        let mut dropped = vec![];
+
+        let shown_brackets = self
+            .tuppers
+            .iter()
+            .filter(|tupper| tupper.show_brackets)
+            .count();
+        if shown_brackets > 0 {
+            dropped.push(format!(
+                "The \"show brackets\" setting of {} tuppers",
+                shown_brackets
+            ));
+        }
+
+        let posts = self.tuppers.iter().map(|tupper| tupper.posts).sum::<i64>();
+        if posts > 0 {
+            dropped.push(format!("Post counts ({} posts in total)", posts));
+        }
+
+        dropped
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TupperboxGroup {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -22,6 +77,8 @@ pub struct Tupper {
     pub show_brackets: bool,
     pub description: Option<String>,
     pub nick: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<i64>,
 }
 
 impl Tupper {
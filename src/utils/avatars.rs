@@ -0,0 +1,384 @@
+use std::{
+    io::Cursor,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use image::{imageops::FilterType, io::Reader, DynamicImage, ImageFormat, ImageOutputFormat};
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::Attachment,
+};
+use reqwest::{header, redirect, Url};
+use sha256::digest;
+use tokio::time::{timeout_at, Instant};
+
+use super::misc::envvar;
+use crate::{
+    models::DBMate,
+    storage::{self, AvatarStorage},
+};
+
+/// How many rehosting failures to list before giving up and just counting them
+const MAX_LISTED_FAILURES: usize = 10;
+
+/// How many redirects to follow when downloading an image; each one is checked like the original
+/// link was
+const MAX_REDIRECTS: usize = 3;
+
+/// How many avatars to download at once when rehosting a batch of them
+const REHOST_CONCURRENCY: usize = 8;
+/// How long rehosting a batch of avatars can take in total; interactions expire after 15 minutes,
+/// and there's still the rest of the import to do after this
+const REHOST_DEADLINE: Duration = Duration::from_secs(5 * 60);
+
+/// The biggest file we'll accept, in bytes
+pub const MAX_IMAGE_SIZE: usize = 8 * 1024 * 1024;
+/// The biggest width or height we'll bother decoding
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+/// Avatars are cropped square and shrunk to (at most) this size
+pub const AVATAR_SIZE: u32 = 512;
+/// Banners keep their shape, but are shrunk to (at most) this wide
+pub const BANNER_WIDTH: u32 = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageKind {
+    Avatar,
+    Banner,
+}
+
+/// Checks that an attachment really is an image, then normalises and uploads it as an avatar
+pub async fn upload_avatar(storage: &dyn AvatarStorage, attachment: Attachment) -> Result<String> {
+    upload_attachment(storage, attachment, ImageKind::Avatar).await
+}
+
+/// Like [`upload_avatar`], but keeps the image's shape
+pub async fn upload_banner(storage: &dyn AvatarStorage, attachment: Attachment) -> Result<String> {
+    upload_attachment(storage, attachment, ImageKind::Banner).await
+}
+
+async fn upload_attachment(
+    storage: &dyn AvatarStorage,
+    attachment: Attachment,
+    kind: ImageKind,
+) -> Result<String> {
+    // no point downloading something we're going to reject anyway
+    if attachment.size as usize > MAX_IMAGE_SIZE {
+        bail!(
+            "That image is too big! Images can be at most {}MB.",
+            MAX_IMAGE_SIZE / 1024 / 1024
+        );
+    }
+
+    let image = attachment
+        .download()
+        .await
+        .context("Failed to download that image from Discord; try again later!")?;
+
+    upload_image(storage, image, kind).await
+}
+
+/// Downloads an image from somewhere else (ie another bot's CDN) and uploads it to our storage, so
+/// it doesn't break when they expire it. Images that are already ours are left alone
+pub async fn rehost_image(
+    storage: &dyn AvatarStorage,
+    url: &str,
+    kind: ImageKind,
+) -> Result<String> {
+    if !needs_rehosting(url) {
+        return Ok(url.to_string());
+    }
+
+    let image = download_image(url).await?;
+
+    upload_image(storage, image, kind).await
+}
+
+/// Whether an image is somewhere other than Multiplex's own storage, so would be copied over
+pub fn needs_rehosting(url: &str) -> bool {
+    storage::key_from_url(url).is_none() && url != envvar("DEFAULT_AVATAR_URL")
+}
+
+/// Downloads an image from a user-given link, following redirects by hand so every hop gets
+/// checked, and giving up as soon as it's clear the image is too big
+async fn download_image(url: &str) -> Result<Vec<u8>> {
+    let mut url = Url::parse(url).context("That isn't a valid link!")?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let response = client_for(&url)
+            .await?
+            .get(url.clone())
+            .send()
+            .await
+            .context("Failed to download that image; is the link right?")?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .context("Failed to download that image; is the link right?")?;
+
+            url = url
+                .join(location)
+                .context("Failed to download that image; is the link right?")?;
+
+            continue;
+        }
+
+        let mut response = response
+            .error_for_status()
+            .context("Failed to download that image; is the link right?")?;
+
+        if response
+            .content_length()
+            .is_some_and(|length| length as usize > MAX_IMAGE_SIZE)
+        {
+            bail!(
+                "That image is too big! Images can be at most {}MB.",
+                MAX_IMAGE_SIZE / 1024 / 1024
+            );
+        }
+
+        // Content-Length is optional (and can lie), so the body is checked as it comes in too
+        let mut image = vec![];
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("Failed to download that image; is the link right?")?
+        {
+            if image.len() + chunk.len() > MAX_IMAGE_SIZE {
+                bail!(
+                    "That image is too big! Images can be at most {}MB.",
+                    MAX_IMAGE_SIZE / 1024 / 1024
+                );
+            }
+
+            image.extend_from_slice(&chunk);
+        }
+
+        return Ok(image);
+    }
+
+    bail!("That link redirects too many times!")
+}
+
+/// Makes a client that can only connect to the addresses `url`'s host resolves to right now, after
+/// checking they're all public, so links can't be used to reach anything on our own network (and
+/// the host can't resolve to somewhere else by the time we connect)
+async fn client_for(url: &Url) -> Result<reqwest::Client> {
+    if url.scheme() != "https" {
+        bail!("Image links have to start with `https://`!");
+    }
+
+    let host = url.host_str().context("That isn't a valid link!")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    // IPv6 hosts keep their brackets in URLs
+    let addresses = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .context("Couldn't find that image's website; is the link right?")?
+            .collect(),
+    };
+
+    if addresses.is_empty() || !addresses.iter().all(|address| is_public(address.ip())) {
+        bail!("That link doesn't point to a public website!");
+    }
+
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .redirect(redirect::Policy::none())
+        .resolve_to_addrs(host, &addresses)
+        .build()
+        .context("Failed to create HTTP client!")
+}
+
+/// Whether an address is on the public internet, rather than loopback, a private network,
+/// link-local (ie cloud metadata endpoints) or otherwise reserved
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // "this network"
+                || a == 0
+                // carrier-grade NAT
+                || (a == 100 && (b & 0xc0) == 64)
+                // benchmarking
+                || (a == 198 && (b & 0xfe) == 18)
+                // reserved
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+
+            let first_segment = ip.segments()[0];
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local
+                || (first_segment & 0xfe00) == 0xfc00
+                // link-local
+                || (first_segment & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Rehosts the avatars of a batch of (usually just imported) mates, a few at a time, giving up on
+/// whatever's left once [`REHOST_DEADLINE`] passes so the interaction doesn't expire first. Mates
+/// whose avatars can't be rehosted keep their old ones, and are listed in what's returned
+pub async fn rehost_mate_avatars(storage: &dyn AvatarStorage, mates: &mut [DBMate]) -> Vec<String> {
+    let deadline = Instant::now() + REHOST_DEADLINE;
+
+    let avatars = mates
+        .iter()
+        .map(|mate| mate.avatar.clone())
+        .collect::<Vec<_>>();
+
+    let mut rehosts = stream::iter(avatars.into_iter().enumerate())
+        .map(|(index, avatar)| async move {
+            (
+                index,
+                rehost_image(storage, &avatar, ImageKind::Avatar).await,
+            )
+        })
+        .buffer_unordered(REHOST_CONCURRENCY);
+
+    let mut results = mates.iter().map(|_| None).collect::<Vec<_>>();
+
+    while let Ok(Some((index, result))) = timeout_at(deadline, rehosts.next()).await {
+        results[index] = Some(result);
+    }
+
+    let mut failures = vec![];
+
+    for (mate, result) in mates.iter_mut().zip(results) {
+        match result {
+            Some(Ok(avatar)) => mate.avatar = avatar,
+            Some(Err(err)) => failures.push(format!("{}: {}", mate.name, err)),
+            None => failures.push(format!("{}: ran out of time", mate.name)),
+        }
+    }
+
+    failures
+}
+
+/// Turns the failures from [`rehost_mate_avatars`] into something to tack onto a reply
+pub fn describe_rehost_failures(failures: &[String]) -> String {
+    if failures.is_empty() {
+        return String::new();
+    }
+
+    let mut description = format!(
+        "\n\nCouldn't copy over {} avatar{} (those mates kept their old links):",
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" }
+    );
+
+    for failure in failures.iter().take(MAX_LISTED_FAILURES) {
+        description.push_str(&format!("\n- {}", failure));
+    }
+
+    if failures.len() > MAX_LISTED_FAILURES {
+        description.push_str(&format!(
+            "\n...and {} more",
+            failures.len() - MAX_LISTED_FAILURES
+        ));
+    }
+
+    description
+}
+
+/// Validates, normalises and uploads an image, returning its public URL
+pub async fn upload_image(
+    storage: &dyn AvatarStorage,
+    image: Vec<u8>,
+    kind: ImageKind,
+) -> Result<String> {
+    // decoding and resizing can take a while, so keep it off the async threads
+    let image = tokio::task::spawn_blocking(move || process_image(&image, kind)).await??;
+
+    let key = format!("{}.png", digest(&image));
+
+    storage
+        .put(&key, &image, "image/png")
+        .await
+        .context("Failed to upload image; try again later!")?;
+
+    Ok(storage::public_url(&key))
+}
+
+/// Makes sure `image` actually is a (reasonably sized) image, then re-encodes it as a PNG so
+/// nothing but the picture itself ends up in storage
+pub fn process_image(image: &[u8], kind: ImageKind) -> Result<Vec<u8>> {
+    if image.len() > MAX_IMAGE_SIZE {
+        bail!(
+            "That image is too big! Images can be at most {}MB.",
+            MAX_IMAGE_SIZE / 1024 / 1024
+        );
+    }
+
+    let format = image::guess_format(image).ok().filter(|format| {
+        matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+        )
+    });
+
+    let Some(format) = format else {
+        bail!("That isn't an image! Images have to be a PNG, JPEG, GIF or WebP.");
+    };
+
+    // check the size before decoding, so nobody can make us decode something enormous
+    let (width, height) = Reader::with_format(Cursor::new(image), format)
+        .into_dimensions()
+        .context("That image seems to be broken; try another one!")?;
+
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        bail!(
+            "That image is too large! Images can be at most {}x{} pixels.",
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION
+        );
+    }
+
+    let decoded = image::load_from_memory_with_format(image, format)
+        .context("That image seems to be broken; try another one!")?;
+
+    let normalised = normalise_image(decoded, kind);
+
+    let mut encoded = vec![];
+    normalised
+        .write_to(&mut Cursor::new(&mut encoded), ImageOutputFormat::Png)
+        .context("Failed to re-encode image!")?;
+
+    Ok(encoded)
+}
+
+fn normalise_image(image: DynamicImage, kind: ImageKind) -> DynamicImage {
+    match kind {
+        ImageKind::Avatar => {
+            let size = image.width().min(image.height()).min(AVATAR_SIZE);
+
+            image.resize_to_fill(size, size, FilterType::Lanczos3)
+        }
+        ImageKind::Banner if image.width() > BANNER_WIDTH => {
+            image.resize(BANNER_WIDTH, u32::MAX, FilterType::Lanczos3)
+        }
+        ImageKind::Banner => image,
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::FindOptions,
+    Collection, Database,
+};
+use poise::{futures_util::TryStreamExt, serenity_prelude::UserId};
+
+use super::misc::envvar_or;
+use crate::models::DBBackup;
+
+/// Bumped whenever the way backups are stored changes, so old ones aren't restored wrongly
+pub const BACKUP_VERSION: i32 = 1;
+
+/// Everything that belongs to a collective, apart from proxied messages (which are only a log, and
+/// can get huge)
+const BACKED_UP_COLLECTIONS: [&str; 6] = [
+    "collectives",
+    "mates",
+    "groups",
+    "settings",
+    "tombstones",
+    "trash",
+];
+
+/// How many backups to keep per collective; older ones are deleted as new ones are made
+pub fn max_backups() -> usize {
+    envvar_or("MAX_BACKUPS", "5")
+        .parse()
+        .expect("MAX_BACKUPS should be a whole number!")
+}
+
+/// Snapshots everything a user owns, so whatever's about to happen to it can be undone
+pub async fn create_backup(database: &Database, user_id: UserId, reason: &str) -> Result<()> {
+    insert_backup(database, user_id, reason).await?;
+
+    prune_backups(&database.collection::<DBBackup>("backups"), user_id, None).await
+}
+
+async fn insert_backup(database: &Database, user_id: UserId, reason: &str) -> Result<()> {
+    let backups_collection = database.collection::<DBBackup>("backups");
+
+    let mut collections = Document::new();
+
+    for name in BACKED_UP_COLLECTIONS {
+        let documents = database
+            .collection::<Document>(name)
+            .find(doc! { "user_id": user_id.get() as i64 }, None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+            .context("Failed to back up your collective; nothing was changed!")?;
+
+        collections.insert(name, documents);
+    }
+
+    backups_collection
+        .insert_one(
+            DBBackup {
+                id: None,
+                user_id: user_id.get() as i64,
+                version: BACKUP_VERSION,
+                reason: reason.to_string(),
+                created_at: Utc::now(),
+                collections,
+            },
+            None,
+        )
+        .await
+        .context("Failed to back up your collective; nothing was changed!")?;
+
+    Ok(())
+}
+
+/// Deletes all but the newest backups, apart from `keep`
+async fn prune_backups(
+    collection: &Collection<DBBackup>,
+    user_id: UserId,
+    keep: Option<ObjectId>,
+) -> Result<()> {
+    let old_ids = get_backups(collection, user_id)
+        .await?
+        .into_iter()
+        .skip(max_backups())
+        .filter_map(|backup| backup.id)
+        .filter(|id| Some(*id) != keep)
+        .collect::<Vec<_>>();
+
+    if !old_ids.is_empty() {
+        collection
+            .delete_many(doc! { "_id": { "$in": old_ids } }, None)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// A user's backups, newest first
+pub async fn get_backups(
+    collection: &Collection<DBBackup>,
+    user_id: UserId,
+) -> Result<Vec<DBBackup>> {
+    // `_id`s start with their creation time, so this is the order they were made in
+    collection
+        .find(
+            doc! { "user_id": user_id.get() as i64 },
+            FindOptions::builder().sort(doc! { "_id": -1 }).build(),
+        )
+        .await
+        .context("Failed to get backups!")?
+        .try_collect::<Vec<_>>()
+        .await
+        .context("Failed to get backups!")
+}
+
+/// Replaces everything a user owns with what's in a backup. What's there now is backed up first,
+/// so a restore can be undone too
+pub async fn restore_backup(database: &Database, backup: &DBBackup) -> Result<()> {
+    if backup.version != BACKUP_VERSION {
+        bail!(
+            "That backup was made by a different version of Multiplex (version {}, this is \
+             version {}), so it can't be restored.",
+            backup.version,
+            BACKUP_VERSION
+        );
+    }
+
+    let user_id = UserId::new(backup.user_id as u64);
+
+    // pruned once the restore's done, so restoring the oldest backup can't delete it first
+    insert_backup(database, user_id, "before restoring a backup").await?;
+
+    // all or nothing, so a failure can't leave half a collective behind
+    let mut session = database
+        .collection::<DBBackup>("backups")
+        .client()
+        .start_session(None)
+        .await
+        .context("Failed to restore backup; try again later!")?;
+
+    session.start_transaction(None).await?;
+
+    for name in BACKED_UP_COLLECTIONS {
+        let collection = database.collection::<Document>(name);
+
+        collection
+            .delete_many_with_session(doc! { "user_id": backup.user_id }, None, &mut session)
+            .await?;
+
+        let documents = backup
+            .collections
+            .get_array(name)
+            .map(|documents| {
+                documents
+                    .iter()
+                    .filter_map(|document| document.as_document().cloned())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if !documents.is_empty() {
+            collection
+                .insert_many_with_session(documents, None, &mut session)
+                .await?;
+        }
+    }
+
+    session
+        .commit_transaction()
+        .await
+        .context("Failed to restore backup; try again later!")?;
+
+    prune_backups(
+        &database.collection::<DBBackup>("backups"),
+        user_id,
+        backup.id,
+    )
+    .await
+}
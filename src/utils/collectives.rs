@@ -1,19 +1,65 @@
-use anyhow::{Context, Result};
-use chrono::Utc;
+use std::collections::HashSet;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
 use mongodb::{
-    bson::{self, doc, oid::ObjectId},
+    bson::{self, doc, oid::ObjectId, Document},
+    options::FindOneOptions,
     Collection,
 };
 use poise::serenity_prelude::UserId;
 
 use crate::models::{DBCollective, DBCollective__new, SwitchLog};
 
+/// How many switches to keep per collective; this needs to be large enough to cover the windows
+/// `/front stats` can be run over
+pub const SWITCH_LOG_LIMIT: usize = 10000;
+
+/// A projection that leaves out the switch logs, which can be up to `SWITCH_LOG_LIMIT` long, for
+/// anything that runs on every message and never needs them
+pub fn without_switch_logs() -> Document {
+    doc! { "switch_logs": 0 }
+}
+
+/// Gets the collective a user owns, or the one their account is linked to
 pub async fn get_or_create_collective(
     collection: &Collection<DBCollective>,
     user_id: UserId,
+) -> Result<DBCollective> {
+    find_or_create_collective(collection, user_id, None).await
+}
+
+/// Like `get_or_create_collective`, but `switch_logs` is always `None`, so the collective must
+/// never be written back as a whole
+pub async fn get_or_create_collective_without_switch_logs(
+    collection: &Collection<DBCollective>,
+    user_id: UserId,
+) -> Result<DBCollective> {
+    find_or_create_collective(
+        collection,
+        user_id,
+        Some(
+            FindOneOptions::builder()
+                .projection(without_switch_logs())
+                .build(),
+        ),
+    )
+    .await
+}
+
+async fn find_or_create_collective(
+    collection: &Collection<DBCollective>,
+    user_id: UserId,
+    options: Option<FindOneOptions>,
 ) -> Result<DBCollective> {
     let collective = collection
-        .find_one(doc! { "user_id": user_id.get() as i64 }, None)
+        .find_one(
+            doc! { "$or": [
+                { "user_id": user_id.get() as i64 },
+                { "linked_accounts": user_id.get() as i64 }
+            ] },
+            options,
+        )
         .await?;
 
     if let Some(collective) = collective {
@@ -33,31 +79,160 @@ pub async fn get_or_create_collective(
     }
 }
 
+/// Gets the ID everything in a user's collective is stored under; for linked accounts this is the
+/// account they're linked to
+pub async fn get_owner_id(
+    collection: &Collection<DBCollective>,
+    user_id: UserId,
+) -> Result<UserId> {
+    let collective = collection
+        .find_one(
+            doc! { "linked_accounts": user_id.get() as i64 },
+            FindOneOptions::builder()
+                .projection(without_switch_logs())
+                .build(),
+        )
+        .await?;
+
+    Ok(collective
+        .map(|collective| UserId::new(collective.user_id as u64))
+        .unwrap_or(user_id))
+}
+
+/// Asks each of `accounts` to link to the collective; they still have to accept with `/link accept`
+pub async fn request_links(
+    collection: &Collection<DBCollective>,
+    collective: &DBCollective,
+    accounts: &[i64],
+) -> Result<()> {
+    let existing_accounts = collective.accounts();
+
+    let accounts = accounts
+        .iter()
+        .filter(|account| !existing_accounts.contains(account))
+        .collect::<Vec<_>>();
+
+    if accounts.is_empty() {
+        return Ok(());
+    }
+
+    collection
+        .update_one(
+            doc! { "user_id": collective.user_id },
+            doc! { "$addToSet": { "pending_links": { "$each": accounts } } },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
 pub async fn update_switch_logs(
     collection: &Collection<DBCollective>,
     collective: &DBCollective,
     mate_id: Option<ObjectId>,
     previous_mate_id: Option<ObjectId>,
-) -> Result<()> {
+    date: DateTime<Utc>,
+) -> Result<Vec<SwitchLog>> {
     let mut switch_logs = collective.switch_logs.clone().unwrap_or_default();
 
-    switch_logs.insert(
-        0,
-        SwitchLog {
-            date: Utc::now(),
-            mate_id,
-            previous_mate_id,
-            unswitch: mate_id.is_none(),
-        },
-    );
+    switch_logs.push(SwitchLog {
+        date,
+        mate_id,
+        previous_mate_id,
+        unswitch: mate_id.is_none(),
+    });
+
+    normalize_switch_logs(&mut switch_logs);
+
+    // a backdated switch can be older than everything that's kept
+    if switch_logs
+        .iter()
+        .position(|log| log.date == date && log.mate_id == mate_id)
+        .is_some_and(|index| index >= SWITCH_LOG_LIMIT)
+    {
+        bail!(
+            "That switch is older than the last {} switches that are kept, so it wasn't logged!",
+            SWITCH_LOG_LIMIT
+        );
+    }
+
+    switch_logs.truncate(SWITCH_LOG_LIMIT);
+
+    save_switch_logs(collection, collective.user_id, &switch_logs).await?;
+
+    Ok(switch_logs)
+}
+
+/// Sorts switch logs newest-first and works out who each switch was from again, so the history
+/// stays consistent after a switch is backdated, edited or deleted
+pub fn normalize_switch_logs(switch_logs: &mut [SwitchLog]) {
+    switch_logs.sort_by_key(|log| std::cmp::Reverse(log.date));
+
+    for log in switch_logs.iter_mut() {
+        log.unswitch = log.mate_id.is_none();
+    }
+
+    let previous_mate_ids = switch_logs
+        .iter()
+        .skip(1)
+        .map(|previous| {
+            if previous.unswitch {
+                None
+            } else {
+                previous.mate_id
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the oldest switch keeps whatever it was from, since there's nothing older to go off of
+    for (log, previous_mate_id) in switch_logs.iter_mut().zip(previous_mate_ids) {
+        log.previous_mate_id = previous_mate_id;
+    }
+}
+
+/// Merges imported switches into a collective's existing ones. Switches at the exact same time are
+/// taken to be the same switch, and `prefer_imported` decides which of the two is kept
+pub fn merge_switch_logs(
+    existing: &[SwitchLog],
+    imported: &[SwitchLog],
+    prefer_imported: bool,
+) -> Vec<SwitchLog> {
+    let (preferred, other) = if prefer_imported {
+        (imported, existing)
+    } else {
+        (existing, imported)
+    };
+
+    let preferred_dates = preferred.iter().map(|log| log.date).collect::<HashSet<_>>();
+
+    let mut switch_logs = preferred
+        .iter()
+        .chain(
+            other
+                .iter()
+                .filter(|log| !preferred_dates.contains(&log.date)),
+        )
+        .cloned()
+        .collect::<Vec<_>>();
+
+    normalize_switch_logs(&mut switch_logs);
 
-    switch_logs.truncate(5);
+    switch_logs.truncate(SWITCH_LOG_LIMIT);
 
+    switch_logs
+}
+
+pub async fn save_switch_logs(
+    collection: &Collection<DBCollective>,
+    user_id: i64,
+    switch_logs: &[SwitchLog],
+) -> Result<()> {
     collection
         .update_one(
-            doc! { "user_id": collective.user_id},
+            doc! { "user_id": user_id },
             doc! {
-                "$set": { "switch_logs": bson::to_bson(&switch_logs).unwrap() }
+                "$set": { "switch_logs": bson::to_bson(switch_logs).unwrap() }
             },
             None,
         )
@@ -65,3 +240,76 @@ pub async fn update_switch_logs(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(date: &str, mate_id: Option<ObjectId>, previous_mate_id: Option<ObjectId>) -> SwitchLog {
+        SwitchLog {
+            date: DateTime::parse_from_rfc3339(date)
+                .unwrap()
+                .with_timezone(&Utc),
+            mate_id,
+            previous_mate_id,
+            unswitch: false,
+        }
+    }
+
+    #[test]
+    fn normalizing_sorts_and_relinks_switches() {
+        let (first, second, before) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+
+        let mut switch_logs = vec![
+            log("2024-04-08T10:00:00Z", Some(first), Some(before)),
+            log("2024-04-08T14:00:00Z", None, None),
+            log("2024-04-08T12:00:00Z", Some(second), None),
+            log("2024-04-08T16:00:00Z", Some(first), Some(second)),
+        ];
+
+        normalize_switch_logs(&mut switch_logs);
+
+        assert_eq!(
+            switch_logs
+                .iter()
+                .map(|log| (log.date.format("%H").to_string(), log.unswitch))
+                .collect::<Vec<_>>(),
+            [
+                ("16".to_string(), false),
+                ("14".to_string(), true),
+                ("12".to_string(), false),
+                ("10".to_string(), false),
+            ]
+        );
+        assert_eq!(
+            switch_logs
+                .iter()
+                .map(|log| log.previous_mate_id)
+                .collect::<Vec<_>>(),
+            // nobody's switched in after an unswitch, and the oldest switch is left alone
+            [None, Some(second), Some(first), Some(before)]
+        );
+    }
+
+    #[test]
+    fn merging_dedupes_switches_at_the_same_time() {
+        let (existing, imported) = (ObjectId::new(), ObjectId::new());
+
+        let existing_logs = [
+            log("2024-04-08T12:00:00Z", Some(existing), None),
+            log("2024-04-08T10:00:00Z", Some(existing), None),
+        ];
+        let imported_logs = [
+            log("2024-04-08T12:00:00Z", Some(imported), None),
+            log("2024-04-08T11:00:00Z", Some(imported), None),
+        ];
+
+        let merged = merge_switch_logs(&existing_logs, &imported_logs, true);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].mate_id, Some(imported));
+
+        let merged = merge_switch_logs(&existing_logs, &imported_logs, false);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].mate_id, Some(existing));
+    }
+}
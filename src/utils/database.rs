@@ -0,0 +1,64 @@
+use anyhow::{bail, Context, Result};
+use mongodb::{bson::doc, Database, IndexModel};
+
+use crate::models::{DBCollective, DBMate, DBMessage};
+
+/// Transactions (used for the trash, transfers and restoring backups) only work on a replica set,
+/// so it's better to find out at startup than the first time someone deletes a mate
+pub async fn check_replica_set(database: &Database) -> Result<()> {
+    let hello = database
+        .run_command(doc! { "hello": 1 }, None)
+        .await
+        .context("Failed to connect to MongoDB")?;
+
+    if !hello.contains_key("setName") {
+        bail!(
+            "MongoDB isn't running as a replica set, which Multiplex needs for transactions! A \
+             single node is fine: start mongod with `--replSet rs0`, run `rs.initiate()` once, \
+             and add `?directConnection=true` (or `?replicaSet=rs0`) to DATABASE_URL. See \
+             docker-compose-dev.yml for an example."
+        );
+    }
+
+    Ok(())
+}
+
+/// Indexes for the lookups made on every message, so people without any mates don't cost a scan
+/// of every collective. Creating an index that already exists does nothing, so this runs on
+/// every startup
+pub async fn create_indexes(database: &Database) -> Result<()> {
+    let collectives_collection = database.collection::<DBCollective>("collectives");
+
+    for keys in [
+        doc! { "user_id": 1 },
+        doc! { "linked_accounts": 1 },
+        doc! { "delegates.user_id": 1 },
+    ] {
+        collectives_collection
+            .create_index(IndexModel::builder().keys(keys).build(), None)
+            .await
+            .context("Failed to create collective indexes")?;
+    }
+
+    database
+        .collection::<DBMate>("mates")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "name": 1 })
+                .build(),
+            None,
+        )
+        .await
+        .context("Failed to create mate indexes")?;
+
+    database
+        .collection::<DBMessage>("messages")
+        .create_index(
+            IndexModel::builder().keys(doc! { "message_id": 1 }).build(),
+            None,
+        )
+        .await
+        .context("Failed to create message indexes")?;
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    Collection, Database,
+};
+use poise::{
+    futures_util::TryStreamExt,
+    serenity_prelude::{ChannelId, EditMember, ExecuteWebhook, GuildId, Http, UserId},
+};
+
+use super::{channels::get_webhook_or_create, misc::envvar};
+use crate::models::{DBChannel, DBCollective, DBMate, DBUserSettings, SwitchLog};
+
+/// Discord's limit on how long nicknames can be
+const NICKNAME_LIMIT: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct FrontStats {
+    /// `None` means nobody was switched in
+    pub mate_id: Option<ObjectId>,
+    pub total: Duration,
+    pub longest: Duration,
+}
+
+/// Works out how long each mate fronted between `start` and `end`.
+/// `switch_logs` is expected newest-first, the same way it's stored on the collective.
+pub fn calculate_front_stats(
+    switch_logs: &[SwitchLog],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<FrontStats> {
+    let mut stats: HashMap<Option<ObjectId>, FrontStats> = HashMap::new();
+
+    // (mate, length) of the stretch we're currently in, so repeated switches to the same mate
+    // count as one continuous stretch
+    let mut current_stretch: Option<(Option<ObjectId>, Duration)> = None;
+
+    let chronological = switch_logs.iter().rev().collect::<Vec<_>>();
+
+    for (i, log) in chronological.iter().enumerate() {
+        let next_date = chronological
+            .get(i + 1)
+            .map(|next| next.date)
+            .unwrap_or(end);
+
+        let interval_start = log.date.max(start);
+        let interval_end = next_date.min(end);
+
+        if interval_end <= interval_start {
+            continue;
+        }
+
+        let fronter = if log.unswitch { None } else { log.mate_id };
+        let length = interval_end - interval_start;
+
+        let stretch = match current_stretch {
+            Some((mate_id, stretch)) if mate_id == fronter => stretch + length,
+            _ => length,
+        };
+        current_stretch = Some((fronter, stretch));
+
+        let entry = stats.entry(fronter).or_insert(FrontStats {
+            mate_id: fronter,
+            total: Duration::zero(),
+            longest: Duration::zero(),
+        });
+
+        entry.total += length;
+        entry.longest = entry.longest.max(stretch);
+    }
+
+    let mut stats = stats.into_values().collect::<Vec<_>>();
+    stats.sort_by_key(|stats| Reverse(stats.total));
+
+    stats
+}
+
+/// When the currently switched-in mate switched in, if anyone is
+pub fn switched_in_since(collective: &DBCollective) -> Option<DateTime<Utc>> {
+    collective
+        .switch_logs
+        .as_ref()?
+        .first()
+        .filter(|log| !log.unswitch)
+        .map(|log| log.date)
+}
+
+/// Lets everything that cares about who's switched in know when that changes
+pub async fn handle_front_change(
+    http: &Http,
+    database: &Database,
+    collective: &DBCollective,
+    previous_mate: Option<DBMate>,
+    mate: Option<DBMate>,
+) -> Result<()> {
+    if previous_mate.as_ref().and_then(|mate| mate.id) == mate.as_ref().and_then(|mate| mate.id) {
+        return Ok(());
+    }
+
+    let settings_collection = database.collection::<DBUserSettings>("settings");
+
+    update_fronter_nicknames(http, &settings_collection, collective, mate.as_ref()).await?;
+
+    if let Some(switch_feed_channel_id) = collective.switch_feed_channel_id {
+        let channels_collection = database.collection::<DBChannel>("channels");
+
+        // a broken feed channel shouldn't stop anyone from switching
+        if let Err(error) = send_switch_feed(
+            http,
+            &channels_collection,
+            collective,
+            switch_feed_channel_id,
+            previous_mate,
+            mate,
+        )
+        .await
+        {
+            tracing::warn!("Failed to send switch feed message: {:?}", error);
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_switch_feed(
+    http: &Http,
+    channels_collection: &Collection<DBChannel>,
+    collective: &DBCollective,
+    switch_feed_channel_id: i64,
+    previous_mate: Option<DBMate>,
+    mate: Option<DBMate>,
+) -> Result<()> {
+    // the feed's posted publicly, so it follows the same privacy as anyone else looking
+    if !collective.is_public || !collective.privacy_settings().front.allows(false) {
+        return Ok(());
+    }
+
+    let (webhook, thread_id) = get_webhook_or_create(
+        http,
+        channels_collection,
+        ChannelId::new(switch_feed_channel_id as u64),
+    )
+    .await?;
+
+    let previous_name = previous_mate
+        .filter(|mate| mate.is_public)
+        .map(|mate| mate.display_name.unwrap_or(mate.name));
+
+    let mut builder = match mate {
+        Some(mate) if mate.is_public => {
+            let name = mate.display_name.clone().unwrap_or(mate.name.clone());
+            let avatar = if mate.privacy_settings().avatar.allows(false) {
+                mate.avatar
+            } else {
+                envvar("DEFAULT_AVATAR_URL")
+            };
+
+            ExecuteWebhook::new()
+                .username(format!(
+                    "{} {}",
+                    name,
+                    collective.collective_tag.clone().unwrap_or_default()
+                ))
+                .avatar_url(avatar)
+                .content(if let Some(previous_name) = previous_name {
+                    format!("**{}** switched in (previously {})", name, previous_name)
+                } else {
+                    format!("**{}** switched in", name)
+                })
+        }
+        // private mates still switch, they just don't get named
+        Some(_) => ExecuteWebhook::new()
+            .username("Multiplex (Switch Feed)")
+            .avatar_url(envvar("DEFAULT_AVATAR_URL"))
+            .content(if let Some(previous_name) = previous_name {
+                format!("**Someone** switched in (previously {})", previous_name)
+            } else {
+                "**Someone** switched in".to_string()
+            }),
+        None => ExecuteWebhook::new()
+            .username("Multiplex (Switch Feed)")
+            .avatar_url(envvar("DEFAULT_AVATAR_URL"))
+            .content(format!(
+                "**{}** switched out",
+                previous_name.unwrap_or("Someone".to_string())
+            )),
+    };
+
+    if let Some(thread_id) = thread_id {
+        builder = builder.in_thread(thread_id);
+    }
+
+    webhook
+        .execute(http, true, builder)
+        .await?
+        .context("Failed to send switch feed message")?;
+
+    Ok(())
+}
+
+/// The nickname to use while a mate is switched in, ie `Alex | tag`
+pub fn fronter_nickname(mate: &DBMate, collective: &DBCollective) -> String {
+    let name = mate.display_name.clone().unwrap_or(mate.name.clone());
+    let tag = collective
+        .collective_tag
+        .clone()
+        .map(|tag| format!(" {}", tag))
+        .unwrap_or_default();
+
+    let max_name_length = NICKNAME_LIMIT.saturating_sub(tag.chars().count());
+
+    // if the tag's too long to fit anything else, just drop it
+    if max_name_length == 0 {
+        return name.chars().take(NICKNAME_LIMIT).collect();
+    }
+
+    if name.chars().count() > max_name_length {
+        format!(
+            "{}…{}",
+            name.chars().take(max_name_length - 1).collect::<String>(),
+            tag
+        )
+    } else {
+        format!("{}{}", name, tag)
+    }
+}
+
+/// Changes the nickname of each of the collective's accounts to the switched-in mate's, in every
+/// guild where that account has opted in, or puts their old nickname back if nobody's switched in
+pub async fn update_fronter_nicknames(
+    http: &Http,
+    settings_collection: &Collection<DBUserSettings>,
+    collective: &DBCollective,
+    mate: Option<&DBMate>,
+) -> Result<()> {
+    let guild_settings = settings_collection
+        .find(
+            doc! {
+                "user_id": { "$in": collective.accounts() },
+                "fronter_nickname": true
+            },
+            None,
+        )
+        .await?
+        .try_collect::<Vec<DBUserSettings>>()
+        .await?;
+
+    for settings in guild_settings {
+        let Some(guild_id) = settings.guild_id else {
+            continue;
+        };
+
+        let nickname = match mate {
+            Some(mate) => fronter_nickname(mate, collective),
+            // an empty nickname resets it back to their username
+            None => settings.original_nickname.clone().unwrap_or_default(),
+        };
+
+        // missing permissions in one guild shouldn't stop the rest
+        if let Err(error) = GuildId::new(guild_id as u64)
+            .edit_member(
+                http,
+                UserId::new(settings.user_id),
+                EditMember::new().nickname(nickname),
+            )
+            .await
+        {
+            tracing::warn!(
+                "Failed to update nickname in guild {}: {:?}",
+                guild_id,
+                error
+            );
+        }
+    }
+
+    Ok(())
+}
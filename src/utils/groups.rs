@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{self, doc},
+    Collection,
+};
+use poise::{futures_util::TryStreamExt, serenity_prelude::UserId};
+
+use crate::{commands::UPSERT_OPTIONS, models::DBGroup};
+
+pub async fn get_group(
+    collection: &Collection<DBGroup>,
+    user_id: UserId,
+    name: String,
+) -> Option<DBGroup> {
+    collection
+        .find_one(doc! { "user_id": user_id.get() as i64, "name": name }, None)
+        .await
+        .ok()?
+}
+
+pub async fn get_all_groups(
+    collection: &Collection<DBGroup>,
+    user_id: UserId,
+) -> Result<Vec<DBGroup>> {
+    collection
+        .find(doc! { "user_id": user_id.get() as i64 }, None)
+        .await
+        .context("Failed to get all groups!")?
+        .try_collect::<Vec<DBGroup>>()
+        .await
+        .context("Failed to get all groups!")
+}
+
+/// Saves imported groups, replacing any existing groups with the same name
+pub async fn upsert_groups(collection: &Collection<DBGroup>, groups: &[DBGroup]) -> Result<()> {
+    for group in groups {
+        collection
+            .find_one_and_update(
+                doc! { "user_id": group.user_id, "name": group.name.clone() },
+                doc! { "$set": bson::to_bson(group)? },
+                UPSERT_OPTIONS.clone().unwrap(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Saves imported groups, leaving any existing groups with the same name alone
+pub async fn insert_missing_groups(
+    collection: &Collection<DBGroup>,
+    user_id: UserId,
+    groups: &[DBGroup],
+) -> Result<()> {
+    let existing = get_all_groups(collection, user_id).await?;
+
+    let missing = groups
+        .iter()
+        .filter(|group| !existing.iter().any(|existing| existing.name == group.name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    upsert_groups(collection, &missing).await
+}
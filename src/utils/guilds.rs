@@ -1,10 +1,15 @@
 use anyhow::{Context, Result};
+use chrono_tz::Tz;
 use mongodb::{bson::doc, Collection};
 use poise::serenity_prelude::{
     ChannelId, CreateEmbed, CreateEmbedFooter, ExecuteWebhook, Http, Message,
 };
 
-use super::{channels::get_webhook_or_create, misc::envvar};
+use super::{
+    channels::get_webhook_or_create,
+    misc::{color_to_embed, envvar},
+    time::format_in_timezone,
+};
 use crate::models::{DBChannel, DBGuild, DBMate};
 
 pub async fn send_server_proxy_log(
@@ -12,6 +17,7 @@ pub async fn send_server_proxy_log(
     message: &Message,
     webhook_message: &Message,
     mate: DBMate,
+    timezone: Option<Tz>,
     channels_collection: &Collection<DBChannel>,
     proxy_logs_channel_id: i64,
 ) -> Result<()> {
@@ -23,7 +29,7 @@ pub async fn send_server_proxy_log(
     )
     .await?;
 
-    let embed = CreateEmbed::new()
+    let mut embed = CreateEmbed::new()
         .title(format!("Message proxied by `{}`", mate.name))
         .description(message.content.clone())
         .thumbnail(mate.avatar)
@@ -31,6 +37,7 @@ pub async fn send_server_proxy_log(
             ("User", format!("<@{}>", message.author.id.get()), false),
             ("Proxied Message", webhook_message.link(), false),
         ])
+        .timestamp(webhook_message.timestamp)
         .footer(CreateEmbedFooter::new(format!(
             "Message ID: {} | Original message ID: {} | Channel ID: {} | User ID: {}",
             webhook_message.id.get(),
@@ -39,6 +46,18 @@ pub async fn send_server_proxy_log(
             message.author.id
         )));
 
+    if let Some(color) = mate.color.as_deref().and_then(color_to_embed) {
+        embed = embed.colour(color);
+    }
+
+    if let Some(timezone) = timezone {
+        embed = embed.field(
+            "Sender's Time",
+            format_in_timezone(*webhook_message.timestamp, timezone),
+            false,
+        );
+    }
+
     let mut builder = ExecuteWebhook::new()
         .username("Multiplex (Proxy Logs)")
         .avatar_url(envvar("DEFAULT_AVATAR_URL"))
@@ -70,6 +89,7 @@ pub async fn get_or_create_dbguild(
             id: guild_id,
             proxy_logs_channel_id: None,
             allowlist_role: None,
+            birthday_channel_id: None,
         };
 
         collection
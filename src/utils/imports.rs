@@ -0,0 +1,750 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    Collection,
+};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter, UserId},
+    ChoiceParameter,
+};
+use serde::Serialize;
+
+use super::{avatars::needs_rehosting, misc::envvar};
+use crate::{
+    commands::UPSERT_OPTIONS,
+    models::{DBCollective, DBMate, SwitchLog},
+};
+
+/// How many entries to show in each part of a preview before just counting the rest
+const MAX_PREVIEW_ENTRIES: usize = 10;
+/// Longer values are cut off, so one bio can't take up the whole preview
+const MAX_VALUE_LENGTH: usize = 40;
+
+/// Fields that are always overwritten, or are handled separately, so aren't worth showing
+const IGNORED_FIELDS: [&str; 4] = ["_id", "user_id", "autoproxy", "switch_logs"];
+
+/// What to do when an imported mate has the same name as one that's already there
+#[derive(Clone, Copy, Debug, ChoiceParameter, PartialEq)]
+pub enum MergeStrategy {
+    #[name = "Overwrite (imported mates replace existing ones)"]
+    Overwrite,
+    #[name = "Keep existing (imported mates with taken names are skipped)"]
+    KeepExisting,
+    #[name = "Rename incoming (imported mates with taken names get a new one)"]
+    RenameIncoming,
+    #[name = "Fill empty fields (only fill in what existing mates are missing)"]
+    FillEmpty,
+}
+
+impl MergeStrategy {
+    /// Switch history is always merged; this decides which switch is kept when the export and the
+    /// existing history both have one at the same time
+    pub fn prefers_incoming(&self) -> bool {
+        *self == MergeStrategy::Overwrite
+    }
+}
+
+/// One field an import is going to change
+#[derive(Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<Bson>,
+    pub new: Bson,
+}
+
+impl FieldChange {
+    pub fn describe(&self) -> String {
+        format!(
+            "`{}`: {} → {}",
+            self.field,
+            describe_value(self.old.as_ref()),
+            describe_value(Some(&self.new))
+        )
+    }
+}
+
+pub enum MateAction {
+    Create,
+    Overwrite,
+    Keep,
+    /// created under a different name, since the one in the export was taken
+    Rename(String),
+    Fill,
+}
+
+/// What an import is going to do with one mate from the export
+pub struct MatePlan {
+    /// the mate's name in the export
+    pub name: String,
+    pub action: MateAction,
+    /// what to `$set` on the mate, if anything
+    pub update: Option<Document>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl MatePlan {
+    /// The name the mate will have once they're imported
+    pub fn target_name(&self) -> &str {
+        match &self.action {
+            MateAction::Rename(name) => name,
+            _ => &self.name,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.action {
+            MateAction::Create => format!("**{}**: created", self.name),
+            MateAction::Overwrite if self.changes.is_empty() => {
+                format!("**{}**: already up to date", self.name)
+            }
+            MateAction::Overwrite => format!(
+                "**{}**: overwritten ({})",
+                self.name,
+                describe_fields(&self.changes)
+            ),
+            MateAction::Keep => format!("**{}**: kept the existing mate", self.name),
+            MateAction::Rename(name) => format!("**{}**: imported as **{}**", self.name, name),
+            MateAction::Fill if self.changes.is_empty() => {
+                format!("**{}**: nothing to fill in", self.name)
+            }
+            MateAction::Fill => format!(
+                "**{}**: filled in {}",
+                self.name,
+                describe_fields(&self.changes)
+            ),
+        }
+    }
+}
+
+/// Works out what to do with each imported mate, matching them to existing mates by name
+pub fn plan_mates(
+    existing: &[DBMate],
+    incoming: &[DBMate],
+    strategy: MergeStrategy,
+) -> Result<Vec<MatePlan>> {
+    let mut taken_names = existing
+        .iter()
+        .chain(incoming)
+        .map(|mate| mate.name.clone())
+        .collect::<HashSet<_>>();
+
+    let mut plans = vec![];
+
+    for mate in incoming {
+        let mut incoming_document = bson::to_document(mate)?;
+
+        let Some(existing) = existing.iter().find(|existing| existing.name == mate.name) else {
+            plans.push(MatePlan {
+                name: mate.name.clone(),
+                action: MateAction::Create,
+                update: Some(incoming_document),
+                changes: vec![],
+            });
+            continue;
+        };
+
+        let existing_document = bson::to_document(existing)?;
+
+        let plan = match strategy {
+            MergeStrategy::Overwrite => MatePlan {
+                name: mate.name.clone(),
+                action: MateAction::Overwrite,
+                changes: diff_documents(&existing_document, &incoming_document),
+                update: Some(incoming_document),
+            },
+            MergeStrategy::KeepExisting => MatePlan {
+                name: mate.name.clone(),
+                action: MateAction::Keep,
+                update: None,
+                changes: vec![],
+            },
+            MergeStrategy::RenameIncoming => {
+                let name = (2..)
+                    .map(|n| format!("{} ({})", mate.name, n))
+                    .find(|name| !taken_names.contains(name))
+                    .expect("Ran out of numbers somehow!");
+
+                taken_names.insert(name.clone());
+                incoming_document.insert("name", name.clone());
+
+                MatePlan {
+                    name: mate.name.clone(),
+                    action: MateAction::Rename(name),
+                    update: Some(incoming_document),
+                    changes: vec![],
+                }
+            }
+            MergeStrategy::FillEmpty => {
+                let filled = fill_empty_fields(&existing_document, &incoming_document);
+
+                MatePlan {
+                    name: mate.name.clone(),
+                    action: MateAction::Fill,
+                    changes: diff_documents(&existing_document, &filled),
+                    update: Some(filled).filter(|filled| !filled.is_empty()),
+                }
+            }
+        };
+
+        plans.push(plan);
+    }
+
+    Ok(plans)
+}
+
+/// Works out what to `$set` on the collective, if anything. There's only one collective, so
+/// renaming doesn't make sense for it, and it's kept as is instead
+pub fn plan_collective(
+    existing: Option<&DBCollective>,
+    incoming: &DBCollective,
+    strategy: MergeStrategy,
+) -> Result<Option<Document>> {
+    let mut incoming = bson::to_document(incoming)?;
+    // merged separately, so setting the collective never touches them
+    incoming.remove("switch_logs");
+
+    let Some(existing) = existing else {
+        return Ok(Some(incoming));
+    };
+
+    Ok(match strategy {
+        MergeStrategy::Overwrite => Some(incoming),
+        MergeStrategy::KeepExisting | MergeStrategy::RenameIncoming => None,
+        MergeStrategy::FillEmpty => {
+            Some(fill_empty_fields(&bson::to_document(existing)?, &incoming))
+                .filter(|filled| !filled.is_empty())
+        }
+    })
+}
+
+/// Writes planned mates to the database
+pub async fn apply_mate_plans(
+    collection: &Collection<DBMate>,
+    user_id: UserId,
+    plans: &[MatePlan],
+) -> Result<()> {
+    for plan in plans {
+        let Some(update) = &plan.update else {
+            continue;
+        };
+
+        collection
+            .find_one_and_update(
+                doc! { "user_id": user_id.get() as i64, "name": plan.target_name() },
+                doc! { "$set": update },
+                UPSERT_OPTIONS.clone().unwrap(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The mates each imported mate ended up as, but under the names they had in the export, so
+/// switches and groups in the export can find them even if they were renamed
+pub fn imported_mates(mates: &[DBMate], plans: &[MatePlan]) -> Vec<DBMate> {
+    plans
+        .iter()
+        .filter_map(|plan| {
+            let mut mate = mates
+                .iter()
+                .find(|mate| mate.name == plan.target_name())?
+                .clone();
+
+            mate.name = plan.name.clone();
+
+            Some(mate)
+        })
+        .collect()
+}
+
+/// Sums up what happened to each mate, to tack onto a reply
+pub fn describe_outcome(plans: &[MatePlan]) -> String {
+    if plans.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "\n\n{}",
+        list_entries(plans.iter().map(|plan| plan.describe()))
+    )
+}
+
+/// Writes documents that are looked up by `keys`. Existing ones are only replaced if `overwrite`
+/// is set, so an export can't be used to take over records that belong to someone else
+pub async fn upsert_by_keys<T: Serialize + Send + Sync>(
+    collection: &Collection<T>,
+    keys: &[&str],
+    documents: &[T],
+    overwrite: bool,
+) -> Result<()> {
+    let collection = collection.clone_with_type::<Document>();
+
+    for document in documents {
+        let mut update = bson::to_document(document)?;
+
+        // the filter's fields are copied into the document when it's inserted
+        let filter = keys
+            .iter()
+            .map(|key| (key.to_string(), update.remove(key).unwrap_or(Bson::Null)))
+            .collect::<Document>();
+
+        let operator = if overwrite { "$set" } else { "$setOnInsert" };
+
+        collection
+            .find_one_and_update(
+                filter,
+                doc! { operator: update },
+                UPSERT_OPTIONS.clone().unwrap(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Everything an import is about to do, worked out before anything's written
+#[derive(Default)]
+pub struct ImportPreview {
+    pub created: Vec<String>,
+    /// mate name -> what changes about them
+    pub overwritten: Vec<(String, Vec<FieldChange>)>,
+    pub filled: Vec<(String, Vec<FieldChange>)>,
+    /// original name -> new name
+    pub renamed: Vec<(String, String)>,
+    pub kept: Vec<String>,
+    pub collective_changes: Vec<FieldChange>,
+    /// anything else worth knowing, ie how many switches are being imported
+    pub notes: Vec<String>,
+    /// what's in the file but can't be imported
+    pub dropped: Vec<String>,
+}
+
+impl ImportPreview {
+    pub fn with_mates(mut self, plans: &[MatePlan], rehost_avatars: bool) -> Self {
+        let mut unknown_avatars = 0;
+
+        for plan in plans {
+            let name = plan.name.clone();
+            let mut changes = plan.changes.clone();
+
+            // rehosted avatars get new links, so there's no telling whether one from another site
+            // is any different from the avatar a mate already has until it's been copied over
+            if rehost_avatars && matches!(plan.action, MateAction::Overwrite) {
+                let count = changes.len();
+
+                changes.retain(|change| {
+                    !(change.field == "avatar"
+                        && matches!(&change.new, Bson::String(url) if needs_rehosting(url)))
+                });
+
+                unknown_avatars += count - changes.len();
+            }
+
+            match &plan.action {
+                MateAction::Create => self.created.push(name),
+                MateAction::Overwrite | MateAction::Fill if changes.is_empty() => {}
+                MateAction::Overwrite => self.overwritten.push((name, changes)),
+                MateAction::Fill => self.filled.push((name, changes)),
+                MateAction::Rename(new_name) => self.renamed.push((name, new_name.clone())),
+                MateAction::Keep => self.kept.push(name),
+            }
+        }
+
+        if unknown_avatars > 0 {
+            self.notes.push(format!(
+                "{} avatar{} from other sites will be copied over, and only replace the existing \
+                 one{} if they're different",
+                unknown_avatars,
+                if unknown_avatars == 1 { "" } else { "s" },
+                if unknown_avatars == 1 { "" } else { "s" },
+            ));
+        }
+
+        self
+    }
+
+    pub fn with_collective(
+        mut self,
+        existing: Option<&DBCollective>,
+        update: Option<&Document>,
+    ) -> Self {
+        let existing = existing
+            .and_then(|existing| bson::to_document(existing).ok())
+            .unwrap_or_default();
+
+        if let Some(update) = update {
+            self.collective_changes = diff_documents(&existing, update);
+        }
+
+        self
+    }
+
+    pub fn with_switch_logs(
+        self,
+        existing: &[SwitchLog],
+        imported: &[DateTime<Utc>],
+        strategy: MergeStrategy,
+    ) -> Self {
+        if imported.is_empty() {
+            if existing.is_empty() {
+                return self;
+            }
+
+            return self.with_note(
+                "Your switch history will be kept as it is, since the export doesn't have any \
+                 switches"
+                    .to_string(),
+            );
+        }
+
+        let existing_dates = existing.iter().map(|log| log.date).collect::<HashSet<_>>();
+        let clashes = imported
+            .iter()
+            .filter(|date| existing_dates.contains(date))
+            .count();
+
+        let mut note = format!(
+            "The {} switches in the export will be merged into your switch history",
+            imported.len()
+        );
+
+        if clashes > 0 {
+            note.push_str(&format!(
+                " ({} at the same time as ones already there will {})",
+                clashes,
+                if strategy.prefers_incoming() {
+                    "replace them"
+                } else {
+                    "be skipped"
+                }
+            ));
+        }
+
+        self.with_note(note)
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_dropped(mut self, dropped: Vec<String>) -> Self {
+        self.dropped.extend(dropped);
+        self
+    }
+
+    pub fn to_embed(&self, source: &str) -> CreateEmbed {
+        let mut sections = vec![];
+
+        if !self.created.is_empty() {
+            sections.push(format!(
+                "**New mates ({})**\n{}",
+                self.created.len(),
+                list_entries(self.created.iter().cloned())
+            ));
+        }
+
+        for (title, mates) in [
+            ("Mates that will be overwritten", &self.overwritten),
+            ("Mates that will be filled in", &self.filled),
+        ] {
+            if mates.is_empty() {
+                continue;
+            }
+
+            sections.push(format!(
+                "**{} ({})**\n{}",
+                title,
+                mates.len(),
+                list_entries(mates.iter().map(|(name, changes)| {
+                    format!(
+                        "{}\n{}",
+                        name,
+                        changes
+                            .iter()
+                            .map(|change| format!("  - {}", change.describe()))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )
+                }))
+            ));
+        }
+
+        if !self.renamed.is_empty() {
+            sections.push(format!(
+                "**Mates that will be imported under a new name ({})**\n{}",
+                self.renamed.len(),
+                list_entries(
+                    self.renamed
+                        .iter()
+                        .map(|(name, new_name)| format!("{} → {}", name, new_name))
+                )
+            ));
+        }
+
+        if !self.kept.is_empty() {
+            sections.push(format!(
+                "**Existing mates that will be kept as they are ({})**\n{}",
+                self.kept.len(),
+                list_entries(self.kept.iter().cloned())
+            ));
+        }
+
+        if !self.collective_changes.is_empty() {
+            sections.push(format!(
+                "**Collective changes**\n{}",
+                list_entries(
+                    self.collective_changes
+                        .iter()
+                        .map(|change| change.describe())
+                )
+            ));
+        }
+
+        if !self.notes.is_empty() {
+            sections.push(list_entries(self.notes.iter().cloned()));
+        }
+
+        if !self.dropped.is_empty() {
+            sections.push(format!(
+                "**Won't be imported**\n{}",
+                list_entries(self.dropped.iter().cloned())
+            ));
+        }
+
+        if sections.is_empty() {
+            sections.push("Nothing would change!".to_string());
+        }
+
+        let mut description = sections.join("\n\n");
+
+        // embed descriptions max out at 4096 characters
+        if description.chars().count() > 4000 {
+            description = description.chars().take(4000).collect::<String>() + "\n...";
+        }
+
+        CreateEmbed::new()
+            .title(format!("Importing from {}", source))
+            .description(description)
+            .footer(CreateEmbedFooter::new(
+                "Nothing has been changed yet. A backup is made before importing.",
+            ))
+    }
+}
+
+fn describe_fields(changes: &[FieldChange]) -> String {
+    changes
+        .iter()
+        .map(|change| change.field.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Which fields would change if `new` was `$set` over `old`. Fields `new` doesn't have are left
+/// alone by `$set`, so they aren't changes
+fn diff_documents(old: &Document, new: &Document) -> Vec<FieldChange> {
+    new.iter()
+        .filter(|(field, _)| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter(|(field, value)| old.get(field.as_str()).unwrap_or(&Bson::Null) != *value)
+        .map(|(field, value)| FieldChange {
+            field: field.clone(),
+            old: old.get(field.as_str()).cloned(),
+            new: value.clone(),
+        })
+        .collect()
+}
+
+/// The fields of `incoming` that `existing` doesn't have (or has left empty)
+fn fill_empty_fields(existing: &Document, incoming: &Document) -> Document {
+    incoming
+        .iter()
+        .filter(|(field, _)| !IGNORED_FIELDS.contains(&field.as_str()) && *field != "name")
+        .filter(|(field, value)| !is_empty(field, Some(value)))
+        .filter(|(field, _)| is_empty(field, existing.get(field.as_str())))
+        .map(|(field, value)| (field.clone(), value.clone()))
+        .collect()
+}
+
+fn is_empty(field: &str, value: Option<&Bson>) -> bool {
+    match value {
+        None | Some(Bson::Null) => true,
+        // mates without an avatar are given the default one
+        Some(Bson::String(avatar)) if field == "avatar" => {
+            avatar.is_empty() || *avatar == envvar("DEFAULT_AVATAR_URL")
+        }
+        Some(Bson::String(string)) => string.is_empty(),
+        Some(Bson::Array(array)) => array.is_empty(),
+        _ => false,
+    }
+}
+
+fn describe_value(value: Option<&Bson>) -> String {
+    let description = match value {
+        None | Some(Bson::Null) => return "*nothing*".to_string(),
+        Some(Bson::String(string)) => string.clone(),
+        Some(Bson::Document(_)) => return "*(changed)*".to_string(),
+        Some(value) => value.to_string(),
+    };
+
+    if description.chars().count() > MAX_VALUE_LENGTH {
+        format!(
+            "`{}...`",
+            description
+                .chars()
+                .take(MAX_VALUE_LENGTH)
+                .collect::<String>()
+                .replace('`', "'")
+        )
+    } else {
+        format!("`{}`", description.replace('`', "'"))
+    }
+}
+
+fn list_entries(entries: impl ExactSizeIterator<Item = String>) -> String {
+    let total = entries.len();
+
+    let mut lines = entries
+        .take(MAX_PREVIEW_ENTRIES)
+        .map(|entry| format!("- {}", entry))
+        .collect::<Vec<_>>();
+
+    if total > MAX_PREVIEW_ENTRIES {
+        lines.push(format!("...and {} more", total - MAX_PREVIEW_ENTRIES));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DBCollective__new;
+
+    const DEFAULT_AVATAR_URL: &str = "https://example.com/default.png";
+
+    fn mate(name: &str) -> DBMate {
+        // every test sets it to the same thing, so it doesn't matter which runs first
+        std::env::set_var("DEFAULT_AVATAR_URL", DEFAULT_AVATAR_URL);
+
+        DBMate {
+            user_id: 1,
+            name: name.to_string(),
+            avatar: DEFAULT_AVATAR_URL.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn collective(name: &str) -> DBCollective {
+        DBCollective__new! {
+            user_id = 1,
+            is_public = true,
+            name = Some(name.to_string()),
+        }
+    }
+
+    #[test]
+    fn renames_skip_names_that_are_already_taken() {
+        let existing = [mate("Ash"), mate("Ash (2)"), mate("Bo")];
+        let incoming = [mate("Ash"), mate("Ash (3)"), mate("Bo")];
+
+        let plans = plan_mates(&existing, &incoming, MergeStrategy::RenameIncoming).unwrap();
+
+        assert!(matches!(&plans[0].action, MateAction::Rename(name) if name == "Ash (4)"));
+        assert_eq!(
+            plans[0].update.as_ref().unwrap().get_str("name"),
+            Ok("Ash (4)")
+        );
+        assert!(matches!(plans[1].action, MateAction::Create));
+        assert!(matches!(&plans[2].action, MateAction::Rename(name) if name == "Bo (2)"));
+    }
+
+    #[test]
+    fn fill_empty_only_fills_empty_fields() {
+        let existing = DBMate {
+            pronouns: Some("she/her".to_string()),
+            ..mate("Ash")
+        };
+        let incoming = DBMate {
+            user_id: 2,
+            autoproxy: true,
+            avatar: "https://example.com/ash.png".to_string(),
+            bio: Some("hi!".to_string()),
+            pronouns: Some("they/them".to_string()),
+            ..mate("Ash")
+        };
+
+        let plans = plan_mates(&[existing], &[incoming], MergeStrategy::FillEmpty).unwrap();
+        let update = plans[0].update.as_ref().unwrap();
+
+        assert_eq!(update.get_str("bio"), Ok("hi!"));
+        // the default avatar counts as not having one
+        assert_eq!(update.get_str("avatar"), Ok("https://example.com/ash.png"));
+        assert!(!update.contains_key("pronouns"));
+
+        for field in ["name", "_id", "user_id", "autoproxy", "switch_logs"] {
+            assert!(!update.contains_key(field), "{} was filled in", field);
+        }
+    }
+
+    #[test]
+    fn fill_empty_never_fills_in_the_default_avatar() {
+        let existing = DBMate {
+            avatar: "https://example.com/ash.png".to_string(),
+            ..mate("Ash")
+        };
+        let incoming = mate("Ash");
+
+        let filled = fill_empty_fields(
+            &bson::to_document(&existing).unwrap(),
+            &bson::to_document(&incoming).unwrap(),
+        );
+
+        assert!(!filled.contains_key("avatar"));
+
+        let filled = fill_empty_fields(
+            &bson::to_document(&mate("Ash")).unwrap(),
+            &bson::to_document(&incoming).unwrap(),
+        );
+
+        assert!(!filled.contains_key("avatar"));
+    }
+
+    #[test]
+    fn keep_existing_leaves_the_collective_alone() {
+        let existing = collective("Ours");
+        let incoming = collective("Theirs");
+
+        for strategy in [MergeStrategy::KeepExisting, MergeStrategy::RenameIncoming] {
+            assert!(plan_collective(Some(&existing), &incoming, strategy)
+                .unwrap()
+                .is_none());
+        }
+
+        // there's nothing to keep if there's no collective yet
+        assert!(
+            plan_collective(None, &incoming, MergeStrategy::KeepExisting)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn plan_collective_never_sets_switch_logs() {
+        let existing = collective("Ours");
+        let incoming = DBCollective {
+            switch_logs: Some(vec![]),
+            ..collective("Theirs")
+        };
+
+        let update = plan_collective(Some(&existing), &incoming, MergeStrategy::Overwrite)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(update.get_str("name"), Ok("Theirs"));
+        assert!(!update.contains_key("switch_logs"));
+    }
+}
@@ -1,12 +1,20 @@
+use std::collections::HashMap;
+
 use anyhow::{Context, Result};
-use mongodb::{bson::doc, results::DeleteResult, Collection};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::{FindOneOptions, FindOptions},
+    Collection,
+};
 use poise::{
     futures_util::TryStreamExt,
     serenity_prelude::{GuildId, UserId},
 };
 
-use super::user_settings::get_or_create_user_settings;
-use crate::models::{AutoproxySettings, DBMate, DBUserSettings, Latch};
+use super::{collectives::without_switch_logs, user_settings::get_or_create_user_settings};
+use crate::models::{
+    AutoproxySettings, DBCollective, DBMate, DBMateTombstone, DBUserSettings, Latch, SwitchLog,
+};
 
 pub async fn get_mate(
     collection: &Collection<DBMate>,
@@ -19,15 +27,61 @@ pub async fn get_mate(
         .ok()?
 }
 
-pub async fn delete_mate(
+pub async fn get_switched_in_mate(
     collection: &Collection<DBMate>,
     user_id: UserId,
+) -> Result<Option<DBMate>> {
+    collection
+        .find_one(
+            doc! { "user_id": user_id.get() as i64, "autoproxy": true },
+            None,
+        )
+        .await
+        .context("Failed to get switched-in mate!")
+}
+
+pub async fn get_tombstone(
+    collection: &Collection<DBMateTombstone>,
+    user_id: UserId,
     name: String,
-) -> Result<DeleteResult> {
+) -> Option<DBMateTombstone> {
     collection
-        .delete_one(doc! { "user_id": user_id.get() as i64, "name": name }, None)
+        .find_one(
+            doc! { "user_id": user_id.get() as i64, "name": name },
+            Some(
+                FindOneOptions::builder()
+                    .sort(doc! { "deleted_at": -1 })
+                    .build(),
+            ),
+        )
+        .await
+        .ok()?
+}
+
+/// Gets the names to show for every mate a user has ever had, including deleted ones
+pub async fn get_mate_names(
+    collection: &Collection<DBMate>,
+    tombstones_collection: &Collection<DBMateTombstone>,
+    user_id: UserId,
+) -> Result<HashMap<ObjectId, String>> {
+    let mut mate_names = tombstones_collection
+        .find(doc! { "user_id": user_id.get() as i64 }, None)
         .await
-        .context("Database failed to delete user; try again later!")
+        .context("Failed to get deleted mates!")?
+        .try_collect::<Vec<DBMateTombstone>>()
+        .await
+        .context("Failed to get deleted mates!")?
+        .into_iter()
+        .map(|tombstone| (tombstone.id, tombstone.label()))
+        .collect::<HashMap<_, _>>();
+
+    for mate in get_all_mates(collection, user_id).await? {
+        if let Some(id) = mate.id {
+            mate_names.insert(id, mate.display_name.unwrap_or(mate.name));
+        }
+    }
+
+    Ok(mate_names)
 }
 
 pub async fn get_all_mates(
@@ -43,6 +97,89 @@ pub async fn get_all_mates(
         .context("Failed to get all mates!")
 }
 
+/// Gets every mate from other collectives that the user has been made a delegate for
+pub async fn get_delegated_mates(
+    collection: &Collection<DBMate>,
+    collectives_collection: &Collection<DBCollective>,
+    user_id: UserId,
+) -> Result<Vec<DBMate>> {
+    let collectives = collectives_collection
+        .find(
+            doc! { "delegates.user_id": user_id.get() as i64 },
+            FindOptions::builder()
+                .projection(without_switch_logs())
+                .build(),
+        )
+        .await?
+        .try_collect::<Vec<DBCollective>>()
+        .await?;
+
+    let mut mates = vec![];
+
+    for collective in collectives {
+        let Some(delegate) = collective
+            .delegates
+            .unwrap_or_default()
+            .into_iter()
+            .find(|delegate| delegate.user_id == user_id.get() as i64)
+        else {
+            continue;
+        };
+
+        if delegate.proxy_mate_ids.is_empty() {
+            continue;
+        }
+
+        mates.extend(
+            collection
+                .find(
+                    doc! {
+                        "user_id": collective.user_id,
+                        "_id": { "$in": delegate.proxy_mate_ids }
+                    },
+                    None,
+                )
+                .await?
+                .try_collect::<Vec<DBMate>>()
+                .await?,
+        );
+    }
+
+    Ok(mates)
+}
+
+/// Makes the switched-in mate match the most recent switch log
+pub async fn sync_switched_in_mate(
+    collection: &Collection<DBMate>,
+    user_id: UserId,
+    switch_logs: &[SwitchLog],
+) -> Result<()> {
+    collection
+        .update_many(
+            doc! { "user_id": user_id.get() as i64, "autoproxy": true },
+            doc! { "$set": { "autoproxy": false } },
+            None,
+        )
+        .await?;
+
+    if let Some(SwitchLog {
+        mate_id: Some(mate_id),
+        unswitch: false,
+        ..
+    }) = switch_logs.first()
+    {
+        collection
+            .update_one(
+                doc! { "_id": *mate_id },
+                doc! { "$set": { "autoproxy": true } },
+                None,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
 pub fn get_matching_mate<'a>(
     mates: &'a Vec<DBMate>,
     message_content: &String,
@@ -1,19 +1,29 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use anyhow::{bail, Context, Result};
-use mongodb::{bson::doc, options::FindOneOptions, results::DeleteResult, Collection, Database};
-use poise::serenity_prelude::{
-    CreateAttachment, CreateEmbed, CreateEmbedAuthor, ExecuteWebhook, Http, Message, MessageId,
-    RoleId, UserId,
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Bson},
+    options::FindOneOptions,
+    results::DeleteResult,
+    Collection, Database,
 };
+use poise::{
+    futures_util::TryStreamExt,
+    serenity_prelude::{
+        CreateAttachment, CreateEmbed, CreateEmbedAuthor, ExecuteWebhook, Http, Message, MessageId,
+        RoleId, UserId,
+    },
+};
+use serde::Deserialize;
 use unicode_segmentation::UnicodeSegmentation;
 
 use super::{
     channels::get_webhook_or_create,
+    collectives::{get_owner_id, without_switch_logs},
     guilds::{get_or_create_dbguild, send_server_proxy_log},
     misc::envvar,
 };
-use crate::models::{DBChannel, DBCollective, DBGuild, DBMate, DBMessage};
+use crate::models::{DBChannel, DBCollective, DBGuild, DBMate, DBMessage, DelegatePermission};
 
 pub fn clamp_message_length(content: &String) -> String {
     let replied_graphemes = content.graphemes(true).collect::<Vec<&str>>();
@@ -141,19 +151,32 @@ pub async fn send_proxied_message(
         .insert_one(
             DBMessage {
                 message_id: new_message.id.get(),
-                user_id: message.author.id.get(),
+                // stored under the collective so any linked account can edit or delete it
+                user_id: collective.user_id as u64,
                 mate_name: Some(mate.name.clone()),
+                mate_id: mate.id,
+                sender_id: Some(message.author.id.get()),
             },
             None,
         )
         .await?;
 
+    // a delegate's time isn't the collective's time
+    let is_own_collective = collective
+        .accounts()
+        .contains(&(message.author.id.get() as i64));
+
     if let Some(proxy_logs_channel_id) = guild_config.proxy_logs_channel_id {
         send_server_proxy_log(
             http,
             message,
             &new_message,
             mate,
+            collective
+                .timezone
+                .as_ref()
+                .filter(|_| is_own_collective)
+                .map(|_| collective.timezone()),
             &channels_collection,
             proxy_logs_channel_id,
         )
@@ -203,9 +226,13 @@ pub async fn get_most_recent_message(
         .context("Failed to find most recent message; try again later!")
 }
 
+/// Gets a proxied message; if `user_id` is given, bails unless they (or the collective they're
+/// linked to) sent it, or they're a delegate of the collective that did with `permission`
 pub async fn get_message(
     collection: &Collection<DBMessage>,
+    collectives_collection: &Collection<DBCollective>,
     user_id: Option<UserId>,
+    permission: DelegatePermission,
     message_id: MessageId,
 ) -> Result<DBMessage> {
     let dbmessage = collection
@@ -214,13 +241,115 @@ pub async fn get_message(
         .context("Could not find message; was it proxied by Multiplex?")?;
 
     if let Some(user_id) = user_id {
-        if dbmessage.user_id != user_id.get() {
+        if !can_manage_message(collectives_collection, &dbmessage, user_id, permission).await? {
             bail!("This message was not sent by you or your mates!")
         }
     }
     Ok(dbmessage)
 }
 
+pub async fn can_manage_message(
+    collectives_collection: &Collection<DBCollective>,
+    dbmessage: &DBMessage,
+    user_id: UserId,
+    permission: DelegatePermission,
+) -> Result<bool> {
+    if get_owner_id(collectives_collection, user_id).await?.get() == dbmessage.user_id {
+        return Ok(true);
+    }
+
+    let collective = collectives_collection
+        .find_one(
+            doc! { "user_id": dbmessage.user_id as i64 },
+            FindOneOptions::builder()
+                .projection(without_switch_logs())
+                .build(),
+        )
+        .await?;
+
+    Ok(collective
+        .and_then(|collective| collective.delegates)
+        .unwrap_or_default()
+        .iter()
+        .any(|delegate| delegate.user_id == user_id.get() as i64 && delegate.allows(permission)))
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct MateMessageStats {
+    pub count: i64,
+    /// message ids are snowflakes, so the biggest one is also the most recent
+    pub last_message_id: i64,
+}
+
+impl MateMessageStats {
+    fn merge(self, other: MateMessageStats) -> MateMessageStats {
+        MateMessageStats {
+            count: self.count + other.count,
+            last_message_id: self.last_message_id.max(other.last_message_id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MessageStats {
+    by_id: HashMap<ObjectId, MateMessageStats>,
+    /// messages from before the mate's id was kept
+    by_name: HashMap<String, MateMessageStats>,
+}
+
+impl MessageStats {
+    pub fn get(&self, mate: &DBMate) -> Option<MateMessageStats> {
+        [
+            mate.id.and_then(|id| self.by_id.get(&id)),
+            self.by_name.get(&mate.name),
+        ]
+        .into_iter()
+        .flatten()
+        .copied()
+        .reduce(MateMessageStats::merge)
+    }
+}
+
+/// How many messages each of a user's mates has sent, and when they last did
+pub async fn get_mate_message_stats(
+    collection: &Collection<DBMessage>,
+    user_id: UserId,
+) -> Result<MessageStats> {
+    let mut cursor = collection
+        .aggregate(
+            [
+                doc! { "$match": { "user_id": user_id.get() as i64 } },
+                doc! { "$group": {
+                    "_id": { "$ifNull": ["$mate_id", "$mate_name"] },
+                    "count": { "$sum": 1 },
+                    "last_message_id": { "$max": "$message_id" }
+                } },
+            ],
+            None,
+        )
+        .await
+        .context("Failed to get message stats!")?;
+
+    let mut stats = MessageStats::default();
+
+    while let Some(document) = cursor.try_next().await? {
+        let key = document.get("_id").cloned();
+        let mate_stats = bson::from_document::<MateMessageStats>(document)?;
+
+        match key {
+            Some(Bson::ObjectId(mate_id)) => {
+                stats.by_id.insert(mate_id, mate_stats);
+            }
+            Some(Bson::String(mate_name)) => {
+                stats.by_name.insert(mate_name, mate_stats);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(stats)
+}
+
 pub async fn delete_dbmessage(
     collection: &Collection<DBMessage>,
     message_id: MessageId,
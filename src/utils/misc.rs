@@ -1,10 +1,7 @@
 use std::env;
 
-use anyhow::{Context, Result};
-use mime2ext::mime2ext;
-use poise::serenity_prelude::{Attachment, ChannelType, GuildChannel, MessageId, UserId};
-use s3::Bucket;
-use sha256::digest;
+use anyhow::{bail, Context, Result};
+use poise::serenity_prelude::{ChannelType, Colour, GuildChannel, MessageId};
 
 /// note: this should have the `s/` passed into it as a prefix
 pub fn handle_sed_edit(_message_content: &String, sed_statement: &String) {
@@ -30,31 +27,25 @@ pub fn envvar(var: &str) -> String {
     ))
 }
 
-pub async fn upload_avatar(
-    avatar_bucket: &Bucket,
-    _user_id: UserId,
-    _mate_name: String,
-    attachment: Attachment,
-) -> Result<String> {
-    // FIXME: actually check whether the attachment is an image to avoid being used as a freer cdn than discord was
-    let file_ext = mime2ext(attachment.content_type.clone().context(
-        "The file does not have a mime type; this should not be possible. What arcane magic did \
-         you use?",
-    )?)
-    .context("Failed to convert detected file type to extension!")?;
+/// Like [`envvar`], but with a default for things that don't have to be configured
+pub fn envvar_or(var: &str, default: &str) -> String {
+    env::var(var).unwrap_or(default.to_string())
+}
 
-    let avatar = attachment.download().await?;
+/// Parses a hex colour like `#ff8800` or `ff8800`, returning it without the `#`
+pub fn parse_color(input: &str) -> Result<String> {
+    let color = input.trim().trim_start_matches('#').to_lowercase();
 
-    avatar_bucket
-        .put_object(format!("/{}.{}", digest(&avatar), file_ext), &avatar)
-        .await?;
+    if color.len() != 6 || u32::from_str_radix(&color, 16).is_err() {
+        bail!("That isn't a valid colour; try a hex code like `#ff8800`")
+    }
 
-    Ok(format!(
-        "{}/{}.{}",
-        envvar("PUBLIC_AVATAR_URL"),
-        digest(avatar),
-        file_ext
-    ))
+    Ok(color)
+}
+
+/// Turns a stored hex colour into something embeds can use
+pub fn color_to_embed(color: &str) -> Option<Colour> {
+    u32::from_str_radix(color, 16).ok().map(Colour::new)
 }
 
 pub fn is_thread(channel: &GuildChannel) -> bool {
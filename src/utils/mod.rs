@@ -1,8 +1,16 @@
+pub mod avatars;
+pub mod backups;
 pub mod channels;
 pub mod collectives;
+pub mod database;
+pub mod front;
+pub mod groups;
 pub mod guild_settings;
 pub mod guilds;
+pub mod imports;
 pub mod mates;
 pub mod messages;
 pub mod misc;
+pub mod time;
+pub mod trash;
 pub mod user_settings;
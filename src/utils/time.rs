@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub fn format_duration(duration: Duration) -> String {
+    let total_minutes = duration.num_minutes();
+
+    let days = total_minutes / (60 * 24);
+    let hours = total_minutes / 60 % 24;
+    let minutes = total_minutes % 60;
+
+    let mut parts = vec![];
+
+    if days > 0 {
+        parts.push(format!("{}d", days));
+    }
+    if hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if minutes > 0 {
+        parts.push(format!("{}m", minutes));
+    }
+
+    if parts.is_empty() {
+        "<1m".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Parses a point in time, either relative to now (ie `20m ago`, `1h 30m ago`) or absolute in
+/// `timezone` (ie `2023-04-08 14:30`, `14:30`)
+pub fn parse_when(input: &str, now: DateTime<Utc>, timezone: Tz) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+
+    let date = if input.eq_ignore_ascii_case("now") {
+        now
+    } else if let Some(relative) = input.to_lowercase().strip_suffix("ago") {
+        now - parse_duration(relative)?
+    } else {
+        parse_absolute(input, now, timezone)?
+    };
+
+    if date > now {
+        bail!("That time is in the future!")
+    }
+
+    Ok(date)
+}
+
+/// Parses a duration like `20m`, `2h30m` or `1 day, 4 hours`
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let mut total = Duration::zero();
+    let mut found_any = false;
+
+    let mut chars = input.chars().peekable();
+
+    while let Some(&character) = chars.peek() {
+        if character.is_whitespace() || character == ',' {
+            chars.next();
+            continue;
+        }
+
+        if !character.is_ascii_digit() {
+            bail!("Couldn't understand that time; try something like `20m ago` or `1h 30m ago`")
+        }
+
+        let mut number = String::new();
+        while let Some(&character) = chars.peek() {
+            if !character.is_ascii_digit() {
+                break;
+            }
+            number.push(character);
+            chars.next();
+        }
+
+        while chars
+            .peek()
+            .is_some_and(|character| character.is_whitespace())
+        {
+            chars.next();
+        }
+
+        let mut unit = String::new();
+        while let Some(&character) = chars.peek() {
+            if !character.is_alphabetic() {
+                break;
+            }
+            unit.push(character);
+            chars.next();
+        }
+
+        let number = number
+            .parse::<i64>()
+            .context("That number is way too big!")?;
+
+        let duration = match unit.as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => Duration::try_seconds(number),
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(number),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::try_hours(number),
+            "d" | "day" | "days" => Duration::try_days(number),
+            "w" | "week" | "weeks" => Duration::try_weeks(number),
+            _ => bail!(
+                "Unknown unit of time `{}`; try something like `20m ago` or `1h 30m ago`",
+                unit
+            ),
+        }
+        .context("That's way too long ago!")?;
+
+        total = total
+            .checked_add(&duration)
+            .context("That's way too long ago!")?;
+        found_any = true;
+    }
+
+    if !found_any {
+        bail!("Couldn't understand that time; try something like `20m ago` or `1h 30m ago`")
+    }
+
+    Ok(total)
+}
+
+fn parse_absolute(input: &str, now: DateTime<Utc>, timezone: Tz) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    let from_local = |date: NaiveDateTime| {
+        timezone
+            .from_local_datetime(&date)
+            .earliest()
+            .map(|date| date.with_timezone(&Utc))
+            .context("That time doesn't exist in your timezone (it was probably skipped by DST)!")
+    };
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(input, format) {
+            return from_local(date);
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return from_local(date.and_time(NaiveTime::MIN));
+    }
+
+    for format in ["%H:%M:%S", "%H:%M"] {
+        if let Ok(time) = NaiveTime::parse_from_str(input, format) {
+            let now = now.with_timezone(&timezone);
+
+            // a bare time means the most recent time it was that time, so yesterday if it hasn't
+            // been that time yet today (going back a day rather than 24 hours, in case of DST)
+            let date = if time > now.time() {
+                now.date_naive()
+                    .pred_opt()
+                    .context("That's way too long ago!")?
+            } else {
+                now.date_naive()
+            };
+
+            return from_local(date.and_time(time));
+        }
+    }
+
+    bail!(
+        "Couldn't understand that time; try something like `20m ago`, `14:30` or `2023-04-08 \
+         14:30` (in {})",
+        timezone.name()
+    )
+}
+
+/// The year PluralKit uses for birthdays without one
+pub const UNKNOWN_BIRTH_YEAR: i32 = 4;
+
+/// Parses a birthday like `2001-04-08`, or `04-08` if the year isn't known
+pub fn parse_birthday(input: &str) -> Result<NaiveDate> {
+    let input = input.trim();
+
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    NaiveDate::parse_from_str(&format!("{:04}-{}", UNKNOWN_BIRTH_YEAR, input), "%Y-%m-%d")
+        .context("Couldn't understand that birthday; try something like `2001-04-08` or `04-08`")
+}
+
+pub fn format_birthday(birthday: NaiveDate) -> String {
+    if birthday.year() == UNKNOWN_BIRTH_YEAR {
+        birthday.format("%B %-d").to_string()
+    } else {
+        birthday.format("%B %-d, %Y").to_string()
+    }
+}
+
+pub fn parse_timezone(input: &str) -> Result<Tz> {
+    input.trim().parse::<Tz>().ok().context(
+        "Couldn't find that timezone; try a name like `Europe/London` or `America/New_York`",
+    )
+}
+
+/// Formats a time the way someone in `timezone` would read it, for places Discord timestamps
+/// don't render (like embed titles)
+pub fn format_in_timezone(date: DateTime<Utc>, timezone: Tz) -> String {
+    date.with_timezone(&timezone)
+        .format("%Y-%m-%d %H:%M %Z")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(input: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(input)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parses_relative_times() {
+        let now = utc("2024-04-08T12:00:00Z");
+
+        assert_eq!(parse_when("now", now, Tz::UTC).unwrap(), now);
+        assert_eq!(
+            parse_when("1h 30m ago", now, Tz::UTC).unwrap(),
+            utc("2024-04-08T10:30:00Z")
+        );
+        assert_eq!(
+            parse_when("1 day, 2 hours ago", now, Tz::UTC).unwrap(),
+            utc("2024-04-07T10:00:00Z")
+        );
+        assert!(parse_when("20 parsecs ago", now, Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn parses_absolute_times_in_the_timezone() {
+        let now = utc("2024-04-08T12:00:00Z");
+
+        assert_eq!(
+            parse_when("2024-04-08 09:30", now, Tz::Europe__London).unwrap(),
+            utc("2024-04-08T08:30:00Z")
+        );
+        assert_eq!(
+            parse_when("2024-01-08", now, Tz::Europe__London).unwrap(),
+            utc("2024-01-08T00:00:00Z")
+        );
+        assert!(parse_when("2024-04-09 09:30", now, Tz::Europe__London).is_err());
+    }
+
+    #[test]
+    fn rejects_times_skipped_by_dst() {
+        let now = utc("2024-04-08T12:00:00Z");
+
+        // clocks went from 01:00 straight to 02:00
+        assert!(parse_absolute("2024-03-31 01:30", now, Tz::Europe__London).is_err());
+        assert!(parse_absolute("01:30", utc("2024-04-01T00:00:00Z"), Tz::Europe__London).is_err());
+    }
+
+    #[test]
+    fn bare_times_are_the_most_recent_one() {
+        // 13:00 in London
+        let now = utc("2024-04-08T12:00:00Z");
+
+        assert_eq!(
+            parse_absolute("09:15", now, Tz::Europe__London).unwrap(),
+            utc("2024-04-08T08:15:00Z")
+        );
+        assert_eq!(
+            parse_absolute("14:30", now, Tz::Europe__London).unwrap(),
+            utc("2024-04-07T13:30:00Z")
+        );
+
+        // the day before was still on GMT, so it's 25 hours back rather than 24
+        assert_eq!(
+            parse_absolute("14:00", utc("2024-03-31T12:00:00Z"), Tz::Europe__London).unwrap(),
+            utc("2024-03-30T14:00:00Z")
+        );
+    }
+
+    #[test]
+    fn parses_birthdays() {
+        assert_eq!(
+            parse_birthday("2001-04-08").unwrap(),
+            NaiveDate::from_ymd_opt(2001, 4, 8).unwrap()
+        );
+        assert_eq!(
+            parse_birthday(" 04-08 ").unwrap(),
+            NaiveDate::from_ymd_opt(UNKNOWN_BIRTH_YEAR, 4, 8).unwrap()
+        );
+        // the unknown year is a leap year, so this is fine
+        assert!(parse_birthday("02-29").is_ok());
+        assert!(parse_birthday("13-01").is_err());
+        assert!(parse_birthday("April 8th").is_err());
+    }
+}
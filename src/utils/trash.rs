@@ -0,0 +1,170 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::{
+    bson::{doc, Document},
+    Collection,
+};
+use poise::{futures_util::TryStreamExt, serenity_prelude::UserId};
+
+use super::misc::envvar_or;
+use crate::models::{DBMate, DBMateTombstone, DBTrashedMate};
+
+/// How many days deleted mates stay in the trash before they're purged for good
+pub fn retention_days() -> i64 {
+    envvar_or("TRASH_RETENTION_DAYS", "30")
+        .parse()
+        .expect("TRASH_RETENTION_DAYS should be a whole number of days!")
+}
+
+/// Moves a mate to the trash, leaving a tombstone so switch logs and proxied messages can still
+/// show who they were
+pub async fn trash_mate(
+    collection: &Collection<DBMate>,
+    tombstones_collection: &Collection<DBMateTombstone>,
+    trash_collection: &Collection<DBTrashedMate>,
+    user_id: UserId,
+    name: String,
+) -> Result<DBTrashedMate> {
+    let filter = doc! { "user_id": user_id.get() as i64, "name": &name };
+
+    // fetched raw, so whatever's stored comes back exactly the same
+    let mate = collection
+        .clone_with_type::<Document>()
+        .find_one(filter.clone(), None)
+        .await?
+        .context("Failed to find mate; do they actually exist?")?;
+
+    let id = mate.get_object_id("_id")?;
+    let deleted_at = Utc::now();
+
+    let trashed = DBTrashedMate {
+        id,
+        user_id: user_id.get() as i64,
+        name: name.clone(),
+        mate: mate.clone(),
+        deleted_at,
+    };
+
+    // all at once, so a failure can't leave the mate both live and in the trash
+    let mut session = collection
+        .client()
+        .start_session(None)
+        .await
+        .context("Failed to delete mate; try again later!")?;
+
+    session.start_transaction(None).await?;
+
+    trash_collection
+        .insert_one_with_session(&trashed, None, &mut session)
+        .await
+        .context("Failed to delete mate; try again later!")?;
+
+    tombstones_collection
+        .insert_one_with_session(
+            DBMateTombstone {
+                id,
+                user_id: user_id.get() as i64,
+                name,
+                display_name: mate
+                    .get_str("display_name")
+                    .ok()
+                    .map(|name| name.to_string()),
+                avatar: mate.get_str("avatar").unwrap_or_default().to_string(),
+                deleted_at,
+            },
+            None,
+            &mut session,
+        )
+        .await
+        .context("Failed to delete mate; try again later!")?;
+
+    collection
+        .delete_one_with_session(filter, None, &mut session)
+        .await
+        .context("Failed to delete mate; try again later!")?;
+
+    session
+        .commit_transaction()
+        .await
+        .context("Failed to delete mate; try again later!")?;
+
+    Ok(trashed)
+}
+
+/// Puts a trashed mate back where they were, with the same id, so switch logs, groups and proxied
+/// messages point at them again
+pub async fn restore_mate(
+    collection: &Collection<DBMate>,
+    tombstones_collection: &Collection<DBMateTombstone>,
+    trash_collection: &Collection<DBTrashedMate>,
+    trashed: DBTrashedMate,
+) -> Result<()> {
+    if collection
+        .find_one(
+            doc! { "user_id": trashed.user_id, "name": &trashed.name },
+            None,
+        )
+        .await?
+        .is_some()
+    {
+        bail!(
+            "There's already a mate called {}! Rename them first, then try again.",
+            trashed.name
+        );
+    }
+
+    let mut mate = trashed.mate;
+    // someone else has probably switched in since
+    mate.insert("autoproxy", false);
+
+    let mut session = collection
+        .client()
+        .start_session(None)
+        .await
+        .context("Failed to restore mate; try again later!")?;
+
+    session.start_transaction(None).await?;
+
+    collection
+        .clone_with_type::<Document>()
+        .insert_one_with_session(mate, None, &mut session)
+        .await
+        .context("Failed to restore mate; try again later!")?;
+
+    tombstones_collection
+        .delete_one_with_session(doc! { "_id": trashed.id }, None, &mut session)
+        .await?;
+    trash_collection
+        .delete_one_with_session(doc! { "_id": trashed.id }, None, &mut session)
+        .await?;
+
+    session
+        .commit_transaction()
+        .await
+        .context("Failed to restore mate; try again later!")?;
+
+    Ok(())
+}
+
+/// Every mate in a user's trash, most recently deleted first
+pub async fn get_trashed_mates(
+    trash_collection: &Collection<DBTrashedMate>,
+    user_id: UserId,
+) -> Result<Vec<DBTrashedMate>> {
+    let mut trashed = trash_collection
+        .find(doc! { "user_id": user_id.get() as i64 }, None)
+        .await
+        .context("Failed to get deleted mates!")?
+        .try_collect::<Vec<_>>()
+        .await
+        .context("Failed to get deleted mates!")?;
+
+    trashed.sort_by_key(|trashed| std::cmp::Reverse(trashed.deleted_at));
+
+    Ok(trashed)
+}
+
+/// When a trashed mate will be purged
+pub fn purge_date(trashed: &DBTrashedMate) -> DateTime<Utc> {
+    trashed.deleted_at + Duration::days(retention_days())
+}
@@ -50,6 +50,9 @@ pub async fn get_or_create_user_settings(
             },
             guild_id: guild_id,
             regex_sed_editing: if guild_id.is_some() { None } else { Some(true) },
+            fronter_nickname: None,
+            original_nickname: None,
+            birthday_announcements: None,
         };
 
         collection
@@ -73,6 +76,9 @@ pub async fn get_or_create_user_settings(
                     autoproxy: Some(AutoproxySettings::SwitchedIn),
                     guild_id: None,
                     regex_sed_editing: Some(true),
+                    fronter_nickname: None,
+                    original_nickname: None,
+                    birthday_announcements: None,
                 };
 
                 collection